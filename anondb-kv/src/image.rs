@@ -0,0 +1,157 @@
+use anyhow::Result;
+
+const MAGIC: &[u8] = b"anondb-kv-image";
+const VERSION: u8 = 1;
+
+const KIND_TABLE: u8 = 0;
+const KIND_MULTIMAP: u8 = 1;
+
+/// A table stored in a `KVImage`. Entries of a multimap table are flattened into one pair per
+/// value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KVImageTable {
+    pub name: String,
+    pub multimap: bool,
+    pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// A backend agnostic byte representation of the full contents of a kv. Used by implementations
+/// that do not have a native byte representation of their state.
+///
+/// Encoded as a magic string and version followed by each table: a kind byte, the table name,
+/// the number of entries, and then each key and value. All lengths are big endian `u64`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KVImage {
+    pub tables: Vec<KVImageTable>,
+}
+
+impl KVImage {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        for table in &self.tables {
+            out.push(if table.multimap {
+                KIND_MULTIMAP
+            } else {
+                KIND_TABLE
+            });
+            write_bytes(&mut out, table.name.as_bytes());
+            out.extend_from_slice(&(table.entries.len() as u64).to_be_bytes());
+            for (key, value) in &table.entries {
+                write_bytes(&mut out, key);
+                write_bytes(&mut out, value);
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            anyhow::bail!("KVImage: bytes are not a kv image");
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            anyhow::bail!("KVImage: unsupported image version {version}");
+        }
+        let mut tables = Vec::default();
+        while !reader.is_empty() {
+            let multimap = match reader.take(1)?[0] {
+                KIND_TABLE => false,
+                KIND_MULTIMAP => true,
+                kind => anyhow::bail!("KVImage: unknown table kind {kind}"),
+            };
+            let name = String::from_utf8(reader.take_bytes()?.to_vec())?;
+            let entry_count = reader.take_u64()?;
+            let mut entries = Vec::default();
+            for _ in 0..entry_count {
+                let key = reader.take_bytes()?.to_vec();
+                let value = reader.take_bytes()?.to_vec();
+                entries.push((key, value));
+            }
+            tables.push(KVImageTable {
+                name,
+                multimap,
+                entries,
+            });
+        }
+        Ok(Self { tables })
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len());
+        let Some(end) = end else {
+            anyhow::bail!("KVImage: unexpected end of bytes");
+        };
+        let out = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(out)
+    }
+
+    fn take_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn take_bytes(&mut self) -> Result<&'a [u8]> {
+        let len = usize::try_from(self.take_u64()?)?;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() -> Result<()> {
+        let image = KVImage {
+            tables: vec![
+                KVImageTable {
+                    name: "users".into(),
+                    multimap: false,
+                    entries: vec![(vec![0, 1], vec![2]), (vec![], vec![])],
+                },
+                KVImageTable {
+                    name: "users_name".into(),
+                    multimap: true,
+                    entries: vec![(vec![5], vec![6]), (vec![5], vec![7])],
+                },
+            ],
+        };
+        assert_eq!(KVImage::from_bytes(&image.to_bytes())?, image);
+        Ok(())
+    }
+
+    #[test]
+    fn truncated() {
+        let image = KVImage {
+            tables: vec![KVImageTable {
+                name: "users".into(),
+                multimap: false,
+                entries: vec![(vec![0, 1], vec![2])],
+            }],
+        };
+        let bytes = image.to_bytes();
+        KVImage::from_bytes(&bytes[..bytes.len() - 1]).expect_err("should fail on truncated bytes");
+        KVImage::from_bytes(b"not an image").expect_err("should fail on bad magic");
+    }
+}
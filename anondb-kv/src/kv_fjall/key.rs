@@ -0,0 +1,153 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+
+/// Name of the fjall partition backing a table. Table names are hex encoded because fjall only
+/// allows a small set of ascii characters in partition names.
+pub fn partition_name(table: &str) -> Result<String> {
+    partition_name_prefixed("t_", table)
+}
+
+/// Name of the fjall partition backing a multimap table.
+pub fn partition_name_multimap(table: &str) -> Result<String> {
+    partition_name_prefixed("m_", table)
+}
+
+fn partition_name_prefixed(prefix: &str, table: &str) -> Result<String> {
+    let mut name = prefix.to_string();
+    for b in table.as_bytes() {
+        write!(name, "{b:02x}")?;
+    }
+    if name.len() > u8::MAX as usize {
+        anyhow::bail!("FjallKV: table name \"{table}\" is too long");
+    }
+    Ok(name)
+}
+
+/// Recover the table name and whether the table is a multimap from a partition name. Returns
+/// `None` for partitions not created by the kv.
+pub fn table_from_partition_name(name: &str) -> Result<Option<(String, bool)>> {
+    let (multimap, hex) = if let Some(hex) = name.strip_prefix("t_") {
+        (false, hex)
+    } else if let Some(hex) = name.strip_prefix("m_") {
+        (true, hex)
+    } else {
+        return Ok(None);
+    };
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or_default(), 16))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(Some((String::from_utf8(bytes)?, multimap)))
+}
+
+/// Escape a multimap key so that it can be followed by a value while preserving sort order.
+/// Each 0x00 byte is written as 0x00 0xFF and the key is terminated by 0x00 0x00.
+///
+/// Entries in a multimap partition are stored as `escape_key(key) || value` with an empty fjall
+/// value. Because the escaped key is never a prefix of another escaped key, entries sort by key
+/// and then by value.
+pub fn escape_key(key: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(key.len() + 2);
+    for b in key {
+        out.push(*b);
+        if *b == 0x00 {
+            out.push(0xFF);
+        }
+    }
+    out.extend_from_slice(&[0x00, 0x00]);
+    out
+}
+
+/// An exclusive upper bound over all entries stored for `key` in a multimap partition.
+pub fn escape_key_end(key: &[u8]) -> Vec<u8> {
+    let mut out = escape_key(key);
+    *out.last_mut().expect("escaped key is never empty") = 0x01;
+    out
+}
+
+/// Join a multimap key and value into a single fjall key.
+pub fn multimap_key(key: &[u8], value: &[u8]) -> Vec<u8> {
+    let mut out = escape_key(key);
+    out.extend_from_slice(value);
+    out
+}
+
+/// Split a fjall key from a multimap partition into the multimap key and value.
+pub fn split_multimap_key(bytes: &[u8]) -> Result<(Vec<u8>, &[u8])> {
+    let mut key = Vec::default();
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match (bytes[i], bytes[i + 1]) {
+            (0x00, 0x00) => return Ok((key, &bytes[i + 2..])),
+            (0x00, 0xFF) => {
+                key.push(0x00);
+                i += 2;
+            }
+            (b, _) => {
+                key.push(b);
+                i += 1;
+            }
+        }
+    }
+    anyhow::bail!("FjallKV: multimap key is missing a terminator")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn partition_name_roundtrip() -> Result<()> {
+        for _ in 0..100 {
+            let table = crate::rand_utf8((rand::random::<u8>() % 30) as usize);
+            assert_eq!(
+                table_from_partition_name(&partition_name(&table)?)?,
+                Some((table.clone(), false))
+            );
+            assert_eq!(
+                table_from_partition_name(&partition_name_multimap(&table)?)?,
+                Some((table, true))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn multimap_key_roundtrip() -> Result<()> {
+        for _ in 0..100 {
+            let key = (0..rand::random::<u8>())
+                .map(|_| rand::random::<u8>() % 3)
+                .collect::<Vec<_>>();
+            let value = rand::random::<[u8; 8]>();
+            let joined = multimap_key(&key, &value);
+            let (k, v) = split_multimap_key(&joined)?;
+            assert_eq!(k, key);
+            assert_eq!(v, value);
+            assert!(escape_key(&key).as_slice() <= joined.as_slice());
+            assert!(joined < escape_key_end(&key));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn multimap_key_sorts_by_key() {
+        for _ in 0..1000 {
+            let k0 = (0..rand::random::<u8>() % 8)
+                .map(|_| rand::random::<u8>() % 3)
+                .collect::<Vec<_>>();
+            let k1 = (0..rand::random::<u8>() % 8)
+                .map(|_| rand::random::<u8>() % 3)
+                .collect::<Vec<_>>();
+            if k0 == k1 {
+                continue;
+            }
+            let v0 = rand::random::<[u8; 4]>();
+            let v1 = rand::random::<[u8; 4]>();
+            assert_eq!(
+                multimap_key(&k0, &v0).cmp(&multimap_key(&k1, &v1)),
+                k0.cmp(&k1)
+            );
+        }
+    }
+}
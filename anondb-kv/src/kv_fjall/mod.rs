@@ -0,0 +1,269 @@
+mod key;
+mod tx;
+
+use key::*;
+use tx::*;
+
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use anyhow::Result;
+
+use fjall::Config;
+use fjall::PartitionCreateOptions;
+use fjall::Slice;
+use fjall::TxKeyspace;
+use fjall::TxPartitionHandle;
+
+use super::*;
+
+/// An opaque reference to a fjall item.
+pub struct FjallItem {
+    key: Slice,
+    value: Slice,
+}
+
+impl OpaqueItem for FjallItem {
+    fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+/// Lazily opened fjall partitions. Shared between the kv and all transactions.
+pub struct Partitions {
+    keyspace: TxKeyspace,
+    handles: RwLock<HashMap<String, TxPartitionHandle>>,
+}
+
+impl Partitions {
+    /// Retrieve a handle to a partition. Returns `None` if the partition does not exist, never
+    /// creates a partition.
+    pub fn get(&self, name: &str) -> Result<Option<TxPartitionHandle>> {
+        if let Some(handle) = self.handles.read().unwrap().get(name) {
+            return Ok(Some(handle.clone()));
+        }
+        if !self.keyspace.partition_exists(name) {
+            return Ok(None);
+        }
+        Ok(Some(self.get_or_create(name)?))
+    }
+
+    /// Retrieve a handle to a partition, creating it if necessary.
+    pub fn get_or_create(&self, name: &str) -> Result<TxPartitionHandle> {
+        if let Some(handle) = self.handles.read().unwrap().get(name) {
+            return Ok(handle.clone());
+        }
+        let handle = self
+            .keyspace
+            .open_partition(name, PartitionCreateOptions::default())?;
+        self.handles
+            .write()
+            .unwrap()
+            .insert(name.into(), handle.clone());
+        Ok(handle)
+    }
+}
+
+/// A kv backed by the fjall LSM-tree. Tables and multimap tables are each stored in a dedicated
+/// partition. Multimap entries are stored as a single key, see `key.rs`.
+///
+/// Write transactions are serializable snapshot isolated. Concurrent write transactions that
+/// conflict will fail on commit.
+pub struct FjallKV {
+    keyspace: TxKeyspace,
+    partitions: Arc<Partitions>,
+}
+
+impl FjallKV {
+    fn open(config: Config) -> Result<Self> {
+        let keyspace = config.open_transactional()?;
+        Ok(Self {
+            partitions: Arc::new(Partitions {
+                keyspace: keyspace.clone(),
+                handles: RwLock::new(HashMap::default()),
+            }),
+            keyspace,
+        })
+    }
+}
+
+impl KV for FjallKV {
+    type ReadTransaction = FjallReadTransaction;
+    type WriteTransaction = FjallWriteTransaction;
+
+    fn at_path(path: &std::path::Path) -> Result<Self> {
+        Self::open(Config::new(path))
+    }
+
+    fn in_memory(bytes_maybe: Option<&[u8]>) -> Result<Self> {
+        // fjall always operates on a directory, use a temporary one that is removed on drop
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "anondb-fjall-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let kv = Self::open(Config::new(path).temporary(true))?;
+        if let Some(bytes) = bytes_maybe {
+            let tx = kv.write_tx()?;
+            for table in KVImage::from_bytes(bytes)?.tables {
+                for (key, value) in &table.entries {
+                    if table.multimap {
+                        tx.insert_multimap(&table.name, key, value)?;
+                    } else {
+                        tx.insert(&table.name, key, value)?;
+                    }
+                }
+            }
+            tx.commit()?;
+        }
+        Ok(kv)
+    }
+
+    fn export_bytes(&self) -> Result<Vec<u8>> {
+        let read = self.keyspace.read_tx();
+        let mut tables = Vec::default();
+        for partition in self.keyspace.list_partitions() {
+            let Some((name, multimap)) = table_from_partition_name(&partition)? else {
+                continue;
+            };
+            let Some(handle) = self.partitions.get(&partition)? else {
+                continue;
+            };
+            let mut entries = Vec::default();
+            for item in read.iter(&handle) {
+                let item = if multimap {
+                    to_multimap_item(item)?
+                } else {
+                    to_item(item)?
+                };
+                entries.push((item.key.to_vec(), item.value.to_vec()));
+            }
+            tables.push(KVImageTable {
+                name,
+                multimap,
+                entries,
+            });
+        }
+        Ok(KVImage { tables }.to_bytes())
+    }
+
+    fn scan<S>(&self, table: &str, predicate: S) -> Result<()>
+    where
+        S: Fn(&[u8], &[u8]) -> Result<bool>,
+    {
+        for item in self.range(table, ..)? {
+            let item = item?;
+            if !predicate(item.key(), item.value())? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_tx(&self) -> Result<Self::WriteTransaction> {
+        Ok(FjallWriteTransaction {
+            write: Mutex::new(self.keyspace.write_tx()?),
+            partitions: self.partitions.clone(),
+        })
+    }
+
+    fn read_tx(&self) -> Result<Self::ReadTransaction> {
+        Ok(FjallReadTransaction {
+            read: self.keyspace.read_tx(),
+            partitions: self.partitions.clone(),
+        })
+    }
+}
+
+/// Convert a range over borrowed keys into owned bounds.
+fn owned_range<'a>(range: impl RangeBounds<&'a [u8]>) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    (
+        range.start_bound().map(|v| v.to_vec()),
+        range.end_bound().map(|v| v.to_vec()),
+    )
+}
+
+/// Convert a range over multimap keys into a range over the escaped keys stored in a multimap
+/// partition.
+fn multimap_range<'a>(range: impl RangeBounds<&'a [u8]>) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    let start = match range.start_bound() {
+        Bound::Included(k) => Bound::Included(escape_key(k)),
+        Bound::Excluded(k) => Bound::Included(escape_key_end(k)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(k) => Bound::Excluded(escape_key_end(k)),
+        Bound::Excluded(k) => Bound::Excluded(escape_key(k)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    (start, end)
+}
+
+fn to_item(item: fjall::Result<fjall::KvPair>) -> Result<FjallItem> {
+    let (key, value) = item?;
+    Ok(FjallItem { key, value })
+}
+
+fn to_multimap_item(item: fjall::Result<fjall::KvPair>) -> Result<FjallItem> {
+    let (key, _) = item?;
+    let (key, value) = split_multimap_key(&key)?;
+    Ok(FjallItem {
+        key: key.into(),
+        value: value.into(),
+    })
+}
+
+/// Operations occuring outside of a transaction. "One and done" operations.
+impl ReadOperations for FjallKV {
+    fn count(&self, table: &str) -> Result<u64> {
+        self.read_tx()?.count(table)
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        self.read_tx()?.get(table, key)
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        let read = self.read_tx()?;
+        read.range_owned(table, owned_range(range), direction)
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        self.read_tx()?.count_multimap(table)
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        let read = self.read_tx()?;
+        read.get_multimap_owned(table, key)
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        let read = self.read_tx()?;
+        read.range_multimap_owned(table, multimap_range(range), direction)
+    }
+}
@@ -0,0 +1,340 @@
+use std::ops::Bound;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+
+use anyhow::Result;
+
+use super::*;
+
+pub struct FjallReadTransaction {
+    pub read: fjall::ReadTransaction,
+    pub partitions: Arc<Partitions>,
+}
+
+pub struct FjallWriteTransaction {
+    pub write: Mutex<fjall::WriteTransaction>,
+    pub partitions: Arc<Partitions>,
+}
+
+/// Iterates over a range of a partition in a write transaction. The fjall write transaction must be
+/// mutably borrowed to iterate, so it is locked on each call to `next` and the iterator seeks past
+/// the previously returned key. Writes made to the transaction between calls are visible.
+pub struct FjallWriteRangeIter<'a> {
+    pub tx: &'a FjallWriteTransaction,
+    /// `None` if the partition does not exist, or after the iterator is exhausted.
+    pub partition: Option<TxPartitionHandle>,
+    pub start: Bound<Vec<u8>>,
+    pub end: Bound<Vec<u8>>,
+    pub direction: SortDirection,
+    pub map_fn: fn(fjall::Result<fjall::KvPair>) -> Result<FjallItem>,
+}
+
+impl Iterator for FjallWriteRangeIter<'_> {
+    type Item = Result<FjallItem>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let partition = self.partition.as_ref()?;
+        let item = if is_valid_range(&self.start, &self.end) {
+            let mut tx = self.tx.tx();
+            let mut range = tx.range(partition, (self.start.clone(), self.end.clone()));
+            match self.direction {
+                SortDirection::Asc => range.next(),
+                SortDirection::Desc => range.next_back(),
+            }
+        } else {
+            None
+        };
+        match &item {
+            Some(Ok((key, _))) => match self.direction {
+                SortDirection::Asc => self.start = Bound::Excluded(key.to_vec()),
+                SortDirection::Desc => self.end = Bound::Excluded(key.to_vec()),
+            },
+            _ => self.partition = None,
+        }
+        item.map(self.map_fn)
+    }
+}
+
+impl FjallReadTransaction {
+    /// Iterate over a range of a table. The returned iterator holds its own snapshot and does not
+    /// borrow the transaction.
+    pub fn range_owned(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<FjallItem>> + use<>> {
+        let partition = self.partitions.get(&partition_name(table)?)?;
+        let inner_iter = partition
+            .map(|p| self.read.range(&p, range))
+            .into_iter()
+            .flatten();
+        Ok(DirectedIter::new(inner_iter, direction).map(to_item))
+    }
+
+    /// Iterate over a range of a multimap table. `range` is expected to be over escaped keys.
+    pub fn range_multimap_owned(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<FjallItem>> + use<>> {
+        let partition = self.partitions.get(&partition_name_multimap(table)?)?;
+        let inner_iter = partition
+            .map(|p| self.read.range(&p, range))
+            .into_iter()
+            .flatten();
+        Ok(DirectedIter::new(inner_iter, direction).map(to_multimap_item))
+    }
+
+    /// Iterate over the values associated with a key in a multimap table.
+    pub fn get_multimap_owned(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<FjallItem>> + use<>> {
+        let partition = self.partitions.get(&partition_name_multimap(table)?)?;
+        Ok(partition
+            .map(|p| self.read.prefix(&p, escape_key(key)))
+            .into_iter()
+            .flatten()
+            .map(to_multimap_item))
+    }
+}
+
+impl ReadOperations for FjallReadTransaction {
+    fn count(&self, table: &str) -> Result<u64> {
+        match self.partitions.get(&partition_name(table)?)? {
+            Some(p) => Ok(self.read.len(&p)? as u64),
+            None => Ok(0),
+        }
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.partitions.get(&partition_name(table)?)? {
+            Some(p) => Ok(self.read.get(&p, key)?.map(|v| v.to_vec())),
+            None => Ok(None),
+        }
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        self.range_owned(table, owned_range(range), direction)
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        match self.partitions.get(&partition_name_multimap(table)?)? {
+            Some(p) => Ok(self.read.len(&p)? as u64),
+            None => Ok(0),
+        }
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        self.get_multimap_owned(table, key)
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        self.range_multimap_owned(table, multimap_range(range), direction)
+    }
+}
+
+impl ReadTx for FjallReadTransaction {
+    type Item = FjallItem;
+
+    fn range_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        Ok(Box::new(self.range_owned(table, range, direction)?))
+    }
+
+    fn range_multimap_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        let range = (
+            range.0.as_ref().map(|v| v.as_slice()),
+            range.1.as_ref().map(|v| v.as_slice()),
+        );
+        Ok(Box::new(self.range_multimap_owned(
+            table,
+            multimap_range(range),
+            direction,
+        )?))
+    }
+}
+
+impl FjallWriteTransaction {
+    fn tx(&self) -> MutexGuard<'_, fjall::WriteTransaction> {
+        self.write.lock().unwrap()
+    }
+
+    /// Remove every key in a partition.
+    fn clear_partition(&self, name: &str) -> Result<()> {
+        let Some(p) = self.partitions.get(name)? else {
+            return Ok(());
+        };
+        let mut tx = self.tx();
+        // fjall `keys` does not observe writes made in the transaction, use `iter` instead
+        let keys = tx
+            .iter(&p)
+            .map(|item| item.map(|(k, _)| k))
+            .collect::<fjall::Result<Vec<_>>>()?;
+        for key in keys {
+            tx.remove(&p, key);
+        }
+        Ok(())
+    }
+}
+
+impl WriteTx for FjallWriteTransaction {
+    fn commit(self) -> Result<()> {
+        self.write.into_inner().unwrap().commit()??;
+        Ok(())
+    }
+}
+
+impl WriteOperations for FjallWriteTransaction {
+    fn insert_multimap(&self, table: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        let p = self
+            .partitions
+            .get_or_create(&partition_name_multimap(table)?)?;
+        self.tx().insert(&p, multimap_key(key, value), []);
+        Ok(())
+    }
+
+    fn remove_multimap(&self, table: &str, key: &[u8], value: &[u8]) -> Result<bool> {
+        let Some(p) = self.partitions.get(&partition_name_multimap(table)?)? else {
+            return Ok(false);
+        };
+        let mut tx = self.tx();
+        Ok(tx.take(&p, multimap_key(key, value))?.is_some())
+    }
+
+    fn remove_all_multimap(&self, table: &str, key: &[u8]) -> Result<()> {
+        let Some(p) = self.partitions.get(&partition_name_multimap(table)?)? else {
+            return Ok(());
+        };
+        let mut tx = self.tx();
+        let keys = tx
+            .prefix(&p, escape_key(key))
+            .map(|item| item.map(|(k, _)| k))
+            .collect::<fjall::Result<Vec<_>>>()?;
+        for key in keys {
+            tx.remove(&p, key);
+        }
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        let p = self.partitions.get_or_create(&partition_name(table)?)?;
+        let mut tx = self.tx();
+        let existing = tx.get(&p, key)?.map(|v| v.to_vec());
+        tx.insert(&p, key, value);
+        Ok(existing)
+    }
+
+    fn remove(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let Some(p) = self.partitions.get(&partition_name(table)?)? else {
+            return Ok(None);
+        };
+        Ok(self.tx().take(&p, key)?.map(|v| v.to_vec()))
+    }
+
+    fn clear(&self, table: &str) -> Result<()> {
+        self.clear_partition(&partition_name(table)?)
+    }
+
+    fn clear_multimap(&self, table: &str) -> Result<()> {
+        self.clear_partition(&partition_name_multimap(table)?)
+    }
+}
+
+impl ReadOperations for FjallWriteTransaction {
+    fn count(&self, table: &str) -> Result<u64> {
+        match self.partitions.get(&partition_name(table)?)? {
+            Some(p) => Ok(self.tx().len(&p)? as u64),
+            None => Ok(0),
+        }
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.partitions.get(&partition_name(table)?)? {
+            Some(p) => Ok(self.tx().get(&p, key)?.map(|v| v.to_vec())),
+            None => Ok(None),
+        }
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        let (start, end) = owned_range(range);
+        Ok(FjallWriteRangeIter {
+            tx: self,
+            partition: self.partitions.get(&partition_name(table)?)?,
+            start,
+            end,
+            direction,
+            map_fn: to_item,
+        })
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        match self.partitions.get(&partition_name_multimap(table)?)? {
+            Some(p) => Ok(self.tx().len(&p)? as u64),
+            None => Ok(0),
+        }
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        // this implementation allocates all items from the entry into memory
+        // the fjall write transaction must be mutably borrowed to iterate
+        let mut out = Vec::default();
+        if let Some(p) = self.partitions.get(&partition_name_multimap(table)?)? {
+            out.extend(self.tx().prefix(&p, escape_key(key)).map(to_multimap_item));
+        }
+        Ok(out.into_iter())
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        let (start, end) = multimap_range(range);
+        Ok(FjallWriteRangeIter {
+            tx: self,
+            partition: self.partitions.get(&partition_name_multimap(table)?)?,
+            start,
+            end,
+            direction,
+            map_fn: to_multimap_item,
+        })
+    }
+}
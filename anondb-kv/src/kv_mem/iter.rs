@@ -0,0 +1,215 @@
+use std::ops::Bound;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use super::*;
+
+/// An owned key and value read from a `MemKV`.
+pub struct MemItem {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+impl OpaqueItem for MemItem {
+    fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+/// Returns `true` if a pending write at `pending` is returned before a stored entry at `stored`.
+/// Pending writes shadow stored entries with the same key.
+fn pending_first<T: Ord>(pending: &T, stored: &T, direction: SortDirection) -> bool {
+    match direction {
+        SortDirection::Asc => pending <= stored,
+        SortDirection::Desc => pending >= stored,
+    }
+}
+
+/// Iterates over a range of a table. The iterator owns a snapshot of the table, and of the
+/// pending writes of a write transaction, and seeks past the previously returned key on each call
+/// to `next`.
+pub struct MemRangeIter {
+    pub table: Option<Arc<MemTable>>,
+    pub overlay: Option<Arc<TableOverlay>>,
+    pub start: Bound<Vec<u8>>,
+    pub end: Bound<Vec<u8>>,
+    pub direction: SortDirection,
+}
+
+impl MemRangeIter {
+    fn next_item(&mut self) -> Option<MemItem> {
+        loop {
+            if !is_valid_range(&self.start, &self.end) {
+                return None;
+            }
+            let range = (as_slice_bound(&self.start), as_slice_bound(&self.end));
+            let stored = match &self.overlay {
+                Some(overlay) if overlay.cleared => None,
+                _ => self.table.as_ref().and_then(|table| {
+                    let mut entries = table.range::<[u8], _>(range);
+                    match self.direction {
+                        SortDirection::Asc => entries.next(),
+                        SortDirection::Desc => entries.next_back(),
+                    }
+                }),
+            };
+            let pending = self.overlay.as_ref().and_then(|overlay| {
+                let mut entries = overlay.entries.range::<[u8], _>(range);
+                match self.direction {
+                    SortDirection::Asc => entries.next(),
+                    SortDirection::Desc => entries.next_back(),
+                }
+            });
+            let (key, value) = match (stored, pending) {
+                (None, None) => return None,
+                (Some((key, value)), Some((pending_key, _)))
+                    if !pending_first(pending_key, key, self.direction) =>
+                {
+                    (key.clone(), Some(value.clone()))
+                }
+                (Some((key, value)), None) => (key.clone(), Some(value.clone())),
+                (_, Some((key, value))) => (key.clone(), value.clone()),
+            };
+            match self.direction {
+                SortDirection::Asc => self.start = Bound::Excluded(key.clone()),
+                SortDirection::Desc => self.end = Bound::Excluded(key.clone()),
+            }
+            // removed by the write transaction
+            let Some(value) = value else {
+                continue;
+            };
+            return Some(MemItem { key, value });
+        }
+    }
+}
+
+impl Iterator for MemRangeIter {
+    type Item = Result<MemItem>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_item().map(Ok)
+    }
+}
+
+/// Iterates over a range of keys in a multimap table, returning each value associated with each
+/// key. Like `MemRangeIter` the iterator owns a snapshot of the table and of the pending writes.
+pub struct MemMultimapIter {
+    pub table: Option<Arc<MemMultimapTable>>,
+    pub overlay: Option<Arc<MultimapOverlay>>,
+    pub start: Bound<Vec<u8>>,
+    pub end: Bound<Vec<u8>>,
+    pub direction: SortDirection,
+    /// The most recently returned key and value.
+    pub current: Option<MultimapEntry>,
+}
+
+impl MemMultimapIter {
+    /// The next stored key and value after the current position.
+    fn next_stored(&self) -> Option<MultimapEntry> {
+        let table = self.table.as_ref()?;
+        let (start, end) = match &self.current {
+            Some((key, value)) => {
+                let next_value = table.get(key).and_then(|values| match self.direction {
+                    SortDirection::Asc => values
+                        .range::<[u8], _>((Bound::Excluded(value.as_slice()), Bound::Unbounded))
+                        .next(),
+                    SortDirection::Desc => values
+                        .range::<[u8], _>((Bound::Unbounded, Bound::Excluded(value.as_slice())))
+                        .next_back(),
+                });
+                if let Some(next_value) = next_value {
+                    return Some((key.clone(), next_value.clone()));
+                }
+                match self.direction {
+                    SortDirection::Asc => {
+                        (Bound::Excluded(key.as_slice()), as_slice_bound(&self.end))
+                    }
+                    SortDirection::Desc => {
+                        (as_slice_bound(&self.start), Bound::Excluded(key.as_slice()))
+                    }
+                }
+            }
+            None => (as_slice_bound(&self.start), as_slice_bound(&self.end)),
+        };
+        if !is_valid_range(&start, &end) {
+            return None;
+        }
+        let mut range = table.range::<[u8], _>((start, end));
+        let (key, value) = match self.direction {
+            SortDirection::Asc => range.find_map(|(key, values)| values.first().map(|v| (key, v))),
+            SortDirection::Desc => range
+                .rev()
+                .find_map(|(key, values)| values.last().map(|v| (key, v))),
+        }?;
+        Some((key.clone(), value.clone()))
+    }
+
+    /// The next pending write after the current position.
+    fn next_pending(&self) -> Option<(&MultimapEntry, &bool)> {
+        let overlay = self.overlay.as_ref()?;
+        // pending writes are keyed by key and value. `key + [0]` is the first key after `key`, so
+        // `(key + [0], [])` sorts after every value of `key`.
+        let after_key = |key: &[u8]| ([key, &[0]].concat(), Vec::default());
+        let mut start = match &self.start {
+            Bound::Included(key) => Bound::Included((key.clone(), Vec::default())),
+            Bound::Excluded(key) => Bound::Included(after_key(key)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let mut end = match &self.end {
+            Bound::Included(key) => Bound::Excluded(after_key(key)),
+            Bound::Excluded(key) => Bound::Excluded((key.clone(), Vec::default())),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        if let Some(current) = &self.current {
+            match self.direction {
+                SortDirection::Asc => start = Bound::Excluded(current.clone()),
+                SortDirection::Desc => end = Bound::Excluded(current.clone()),
+            }
+        }
+        if !is_valid_range(&start, &end) {
+            return None;
+        }
+        let mut entries = overlay.entries.range((start, end));
+        match self.direction {
+            SortDirection::Asc => entries.next(),
+            SortDirection::Desc => entries.next_back(),
+        }
+    }
+
+    fn next_item(&mut self) -> Option<MemItem> {
+        loop {
+            let stored = match &self.overlay {
+                Some(overlay) if overlay.cleared => None,
+                _ => self.next_stored(),
+            };
+            let (entry, inserted) = match (stored, self.next_pending()) {
+                (None, None) => return None,
+                (Some(stored), Some((pending, _)))
+                    if !pending_first(pending, &stored, self.direction) =>
+                {
+                    (stored, true)
+                }
+                (Some(stored), None) => (stored, true),
+                (_, Some((pending, inserted))) => (pending.clone(), *inserted),
+            };
+            self.current = Some(entry.clone());
+            // removed by the write transaction
+            if !inserted {
+                continue;
+            }
+            let (key, value) = entry;
+            return Some(MemItem { key, value });
+        }
+    }
+}
+
+impl Iterator for MemMultimapIter {
+    type Item = Result<MemItem>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_item().map(Ok)
+    }
+}
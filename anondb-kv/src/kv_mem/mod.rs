@@ -0,0 +1,257 @@
+mod iter;
+mod tx;
+
+use iter::*;
+use tx::*;
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use anyhow::Result;
+
+use super::*;
+
+pub type MemTable = BTreeMap<Vec<u8>, Vec<u8>>;
+pub type MemMultimapTable = BTreeMap<Vec<u8>, BTreeSet<Vec<u8>>>;
+
+/// The full contents of a `MemKV` at a point in time. Tables are reference counted and copied on
+/// write, so cloning a state is `O(T)` over the number of tables.
+#[derive(Debug, Clone, Default)]
+pub struct MemState {
+    pub tables: HashMap<String, Arc<MemTable>>,
+    pub multimap_tables: HashMap<String, Arc<MemMultimapTable>>,
+}
+
+impl MemState {
+    fn count(&self, table: &str) -> u64 {
+        self.tables.get(table).map(|t| t.len() as u64).unwrap_or(0)
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Option<Vec<u8>> {
+        self.tables.get(table).and_then(|t| t.get(key).cloned())
+    }
+
+    fn range<'a>(
+        &self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
+    ) -> MemRangeIter {
+        MemRangeIter {
+            table: self.tables.get(table).cloned(),
+            overlay: None,
+            start: range.start_bound().map(|v| v.to_vec()),
+            end: range.end_bound().map(|v| v.to_vec()),
+            direction,
+        }
+    }
+
+    fn count_multimap(&self, table: &str) -> u64 {
+        self.multimap_tables
+            .get(table)
+            .map(|t| t.values().map(|values| values.len() as u64).sum())
+            .unwrap_or(0)
+    }
+
+    fn range_multimap<'a>(
+        &self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
+    ) -> MemMultimapIter {
+        MemMultimapIter {
+            table: self.multimap_tables.get(table).cloned(),
+            overlay: None,
+            start: range.start_bound().map(|v| v.to_vec()),
+            end: range.end_bound().map(|v| v.to_vec()),
+            direction,
+            current: None,
+        }
+    }
+
+    fn table_mut(&mut self, table: &str) -> &mut MemTable {
+        Arc::make_mut(self.tables.entry(table.into()).or_default())
+    }
+
+    fn multimap_table_mut(&mut self, table: &str) -> &mut MemMultimapTable {
+        Arc::make_mut(self.multimap_tables.entry(table.into()).or_default())
+    }
+}
+
+/// Serializes write transactions. A write transaction holds the lock until it is committed or
+/// dropped.
+#[derive(Debug, Default)]
+pub struct WriteLock {
+    locked: Mutex<bool>,
+    released: Condvar,
+}
+
+impl WriteLock {
+    fn acquire(self: &Arc<Self>) -> WriteGuard {
+        let mut locked = self.locked.lock().unwrap();
+        while *locked {
+            locked = self.released.wait(locked).unwrap();
+        }
+        *locked = true;
+        WriteGuard(self.clone())
+    }
+}
+
+#[derive(Debug)]
+pub struct WriteGuard(Arc<WriteLock>);
+
+impl Drop for WriteGuard {
+    fn drop(&mut self) {
+        *self.0.locked.lock().unwrap() = false;
+        self.0.released.notify_one();
+    }
+}
+
+/// A dependency free kv stored entirely in memory. Intended as the reference implementation of
+/// the `KV` trait semantics, and as a fast backend for tests.
+///
+/// Read transactions hold a snapshot of the state when they were created. Write transactions are
+/// serialized and record their writes in an overlay over the state they started from, which is
+/// merged into the state on commit. Tables are only copied on commit if a read transaction still
+/// holds them.
+#[derive(Debug, Default)]
+pub struct MemKV {
+    state: Arc<RwLock<Arc<MemState>>>,
+    write_lock: Arc<WriteLock>,
+}
+
+impl MemKV {
+    fn snapshot(&self) -> Arc<MemState> {
+        self.state.read().unwrap().clone()
+    }
+}
+
+impl KV for MemKV {
+    type ReadTransaction = MemReadTransaction;
+    type WriteTransaction = MemWriteTransaction;
+
+    fn at_path(_path: &std::path::Path) -> Result<Self> {
+        anyhow::bail!("MemKV: cannot be persisted to a path");
+    }
+
+    fn in_memory(bytes_maybe: Option<&[u8]>) -> Result<Self> {
+        let mut state = MemState::default();
+        if let Some(bytes) = bytes_maybe {
+            for table in KVImage::from_bytes(bytes)?.tables {
+                if table.multimap {
+                    let table_mut = state.multimap_table_mut(&table.name);
+                    for (key, value) in table.entries {
+                        table_mut.entry(key).or_default().insert(value);
+                    }
+                } else {
+                    state.table_mut(&table.name).extend(table.entries);
+                }
+            }
+        }
+        Ok(Self {
+            state: Arc::new(RwLock::new(Arc::new(state))),
+            write_lock: Arc::default(),
+        })
+    }
+
+    fn export_bytes(&self) -> Result<Vec<u8>> {
+        let state = self.snapshot();
+        let mut tables = Vec::default();
+        for (name, table) in &state.tables {
+            tables.push(KVImageTable {
+                name: name.clone(),
+                multimap: false,
+                entries: table.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            });
+        }
+        for (name, table) in &state.multimap_tables {
+            tables.push(KVImageTable {
+                name: name.clone(),
+                multimap: true,
+                entries: table
+                    .iter()
+                    .flat_map(|(k, values)| values.iter().map(|v| (k.clone(), v.clone())))
+                    .collect(),
+            });
+        }
+        Ok(KVImage { tables }.to_bytes())
+    }
+
+    fn scan<S>(&self, table: &str, predicate: S) -> Result<()>
+    where
+        S: Fn(&[u8], &[u8]) -> Result<bool>,
+    {
+        for item in self.range(table, ..)? {
+            let item = item?;
+            if !predicate(item.key(), item.value())? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_tx(&self) -> Result<Self::WriteTransaction> {
+        let guard = self.write_lock.acquire();
+        Ok(MemWriteTransaction {
+            base: self.snapshot(),
+            overlay: RwLock::default(),
+            target: self.state.clone(),
+            _guard: guard,
+        })
+    }
+
+    fn read_tx(&self) -> Result<Self::ReadTransaction> {
+        Ok(MemReadTransaction {
+            state: self.snapshot(),
+        })
+    }
+}
+
+/// Operations occuring outside of a transaction. "One and done" operations.
+impl ReadOperations for MemKV {
+    fn count(&self, table: &str) -> Result<u64> {
+        Ok(self.snapshot().count(table))
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.snapshot().get(table, key))
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        Ok(self.snapshot().range(table, range, direction))
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        Ok(self.snapshot().count_multimap(table))
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        Ok(self
+            .snapshot()
+            .range_multimap(table, key..=key, SortDirection::Asc))
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        Ok(self.snapshot().range_multimap(table, range, direction))
+    }
+}
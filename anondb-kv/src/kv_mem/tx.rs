@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use anyhow::Result;
+
+use super::*;
+
+pub struct MemReadTransaction {
+    pub state: Arc<MemState>,
+}
+
+pub struct MemWriteTransaction {
+    /// The kv state when the transaction started.
+    pub base: Arc<MemState>,
+    /// Writes made by this transaction, merged into the state on commit.
+    pub overlay: RwLock<MemOverlay>,
+    /// The kv state that is replaced on commit.
+    pub target: Arc<RwLock<Arc<MemState>>>,
+    pub _guard: WriteGuard,
+}
+
+/// Pending writes of a write transaction, by table name. Tables are reference counted so
+/// iterators may hold a snapshot of the pending writes.
+#[derive(Debug, Default)]
+pub struct MemOverlay {
+    pub tables: HashMap<String, Arc<TableOverlay>>,
+    pub multimap_tables: HashMap<String, Arc<MultimapOverlay>>,
+}
+
+/// Pending writes to a table.
+#[derive(Debug, Clone, Default)]
+pub struct TableOverlay {
+    /// The table was cleared, entries of the base state are hidden.
+    pub cleared: bool,
+    /// Written values by key, `None` if the key was removed.
+    pub entries: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+    /// Number of keys in the table including pending writes.
+    pub len: u64,
+}
+
+/// A key and one of its values in a multimap table.
+pub type MultimapEntry = (Vec<u8>, Vec<u8>);
+
+/// Pending writes to a multimap table.
+#[derive(Debug, Clone, Default)]
+pub struct MultimapOverlay {
+    /// The table was cleared, entries of the base state are hidden.
+    pub cleared: bool,
+    /// Written key and value pairs, `false` if the pair was removed.
+    pub entries: BTreeMap<MultimapEntry, bool>,
+    /// Number of values in the table including pending writes.
+    pub len: u64,
+}
+
+impl TableOverlay {
+    /// Retrieve a value, including pending writes.
+    fn get(&self, base: &MemState, table: &str, key: &[u8]) -> Option<Vec<u8>> {
+        match self.entries.get(key) {
+            Some(value) => value.clone(),
+            None if self.cleared => None,
+            None => base.get(table, key),
+        }
+    }
+}
+
+impl MultimapOverlay {
+    /// Determine if a key and value are present, including pending writes.
+    fn contains(&self, base: &MemState, table: &str, key: &[u8], value: &[u8]) -> bool {
+        match self.entries.get(&(key.to_vec(), value.to_vec())) {
+            Some(inserted) => *inserted,
+            None if self.cleared => false,
+            None => base
+                .multimap_tables
+                .get(table)
+                .and_then(|t| t.get(key))
+                .is_some_and(|values| values.contains(value)),
+        }
+    }
+
+    /// Every value of a key, including pending writes.
+    fn values(&self, base: &MemState, table: &str, key: &[u8]) -> BTreeSet<Vec<u8>> {
+        let mut values = BTreeSet::default();
+        if !self.cleared {
+            if let Some(stored) = base.multimap_tables.get(table).and_then(|t| t.get(key)) {
+                values.extend(stored.iter().cloned());
+            }
+        }
+        let start = (key.to_vec(), Vec::default());
+        for ((pending_key, value), inserted) in self.entries.range(start..) {
+            if pending_key.as_slice() != key {
+                break;
+            }
+            if *inserted {
+                values.insert(value.clone());
+            } else {
+                values.remove(value);
+            }
+        }
+        values
+    }
+}
+
+impl MemWriteTransaction {
+    fn table_overlay(&self, table: &str) -> Option<Arc<TableOverlay>> {
+        self.overlay.read().unwrap().tables.get(table).cloned()
+    }
+
+    fn multimap_overlay(&self, table: &str) -> Option<Arc<MultimapOverlay>> {
+        self.overlay
+            .read()
+            .unwrap()
+            .multimap_tables
+            .get(table)
+            .cloned()
+    }
+
+    /// Apply a write to the pending writes of a table. The pending writes are only copied if an
+    /// iterator holds a snapshot of them.
+    fn write_table<R>(&self, table: &str, f: impl FnOnce(&mut TableOverlay, &MemState) -> R) -> R {
+        let mut overlay = self.overlay.write().unwrap();
+        let table_overlay = overlay.tables.entry(table.into()).or_insert_with(|| {
+            Arc::new(TableOverlay {
+                len: self.base.count(table),
+                ..Default::default()
+            })
+        });
+        f(Arc::make_mut(table_overlay), &self.base)
+    }
+
+    fn write_multimap_table<R>(
+        &self,
+        table: &str,
+        f: impl FnOnce(&mut MultimapOverlay, &MemState) -> R,
+    ) -> R {
+        let mut overlay = self.overlay.write().unwrap();
+        let table_overlay = overlay
+            .multimap_tables
+            .entry(table.into())
+            .or_insert_with(|| {
+                Arc::new(MultimapOverlay {
+                    len: self.base.count_multimap(table),
+                    ..Default::default()
+                })
+            });
+        f(Arc::make_mut(table_overlay), &self.base)
+    }
+
+    fn range<'a>(
+        &self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
+    ) -> MemRangeIter {
+        MemRangeIter {
+            overlay: self.table_overlay(table),
+            ..self.base.range(table, range, direction)
+        }
+    }
+
+    fn range_multimap<'a>(
+        &self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
+    ) -> MemMultimapIter {
+        MemMultimapIter {
+            overlay: self.multimap_overlay(table),
+            ..self.base.range_multimap(table, range, direction)
+        }
+    }
+}
+
+impl ReadOperations for MemReadTransaction {
+    fn count(&self, table: &str) -> Result<u64> {
+        Ok(self.state.count(table))
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.state.get(table, key))
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        Ok(self.state.range(table, range, direction))
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        Ok(self.state.count_multimap(table))
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        Ok(self
+            .state
+            .range_multimap(table, key..=key, SortDirection::Asc))
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        Ok(self.state.range_multimap(table, range, direction))
+    }
+}
+
+impl ReadTx for MemReadTransaction {
+    type Item = MemItem;
+
+    fn range_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        Ok(Box::new(self.state.range(
+            table,
+            (as_slice_bound(&range.0), as_slice_bound(&range.1)),
+            direction,
+        )))
+    }
+
+    fn range_multimap_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        Ok(Box::new(self.state.range_multimap(
+            table,
+            (as_slice_bound(&range.0), as_slice_bound(&range.1)),
+            direction,
+        )))
+    }
+}
+
+impl WriteTx for MemWriteTransaction {
+    fn commit(self) -> Result<()> {
+        let Self {
+            base,
+            overlay,
+            target,
+            _guard,
+        } = self;
+        // write transactions are serialized, so the target still holds the base state. Release
+        // it so the state is only copied if a read transaction holds it.
+        drop(base);
+        let overlay = overlay.into_inner().unwrap();
+        let mut target = target.write().unwrap();
+        let state = Arc::make_mut(&mut target);
+        for (name, table_overlay) in overlay.tables {
+            let table_overlay = Arc::unwrap_or_clone(table_overlay);
+            if table_overlay.cleared {
+                state.tables.remove(&name);
+            }
+            if table_overlay.entries.is_empty() {
+                continue;
+            }
+            let table = state.table_mut(&name);
+            for (key, value) in table_overlay.entries {
+                match value {
+                    Some(value) => table.insert(key, value),
+                    None => table.remove(&key),
+                };
+            }
+        }
+        for (name, table_overlay) in overlay.multimap_tables {
+            let table_overlay = Arc::unwrap_or_clone(table_overlay);
+            if table_overlay.cleared {
+                state.multimap_tables.remove(&name);
+            }
+            if table_overlay.entries.is_empty() {
+                continue;
+            }
+            let table = state.multimap_table_mut(&name);
+            for ((key, value), inserted) in table_overlay.entries {
+                if inserted {
+                    table.entry(key).or_default().insert(value);
+                } else if let Some(values) = table.get_mut(&key) {
+                    values.remove(&value);
+                    if values.is_empty() {
+                        table.remove(&key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl WriteOperations for MemWriteTransaction {
+    fn insert_multimap(&self, table: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.write_multimap_table(table, |overlay, base| {
+            if !overlay.contains(base, table, key, value) {
+                overlay.len += 1;
+            }
+            overlay.entries.insert((key.to_vec(), value.to_vec()), true);
+        });
+        Ok(())
+    }
+
+    fn remove_multimap(&self, table: &str, key: &[u8], value: &[u8]) -> Result<bool> {
+        Ok(self.write_multimap_table(table, |overlay, base| {
+            if !overlay.contains(base, table, key, value) {
+                return false;
+            }
+            overlay
+                .entries
+                .insert((key.to_vec(), value.to_vec()), false);
+            overlay.len -= 1;
+            true
+        }))
+    }
+
+    fn remove_all_multimap(&self, table: &str, key: &[u8]) -> Result<()> {
+        self.write_multimap_table(table, |overlay, base| {
+            for value in overlay.values(base, table, key) {
+                overlay.entries.insert((key.to_vec(), value), false);
+                overlay.len -= 1;
+            }
+        });
+        Ok(())
+    }
+
+    fn insert(&self, table: &str, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.write_table(table, |overlay, base| {
+            let old_value = overlay.get(base, table, key);
+            if old_value.is_none() {
+                overlay.len += 1;
+            }
+            overlay.entries.insert(key.to_vec(), Some(value.to_vec()));
+            old_value
+        }))
+    }
+
+    fn remove(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.write_table(table, |overlay, base| {
+            let old_value = overlay.get(base, table, key);
+            if old_value.is_some() {
+                overlay.entries.insert(key.to_vec(), None);
+                overlay.len -= 1;
+            }
+            old_value
+        }))
+    }
+
+    fn clear(&self, table: &str) -> Result<()> {
+        self.overlay.write().unwrap().tables.insert(
+            table.into(),
+            Arc::new(TableOverlay {
+                cleared: true,
+                ..Default::default()
+            }),
+        );
+        Ok(())
+    }
+
+    fn clear_multimap(&self, table: &str) -> Result<()> {
+        self.overlay.write().unwrap().multimap_tables.insert(
+            table.into(),
+            Arc::new(MultimapOverlay {
+                cleared: true,
+                ..Default::default()
+            }),
+        );
+        Ok(())
+    }
+}
+
+impl ReadOperations for MemWriteTransaction {
+    fn count(&self, table: &str) -> Result<u64> {
+        Ok(match self.table_overlay(table) {
+            Some(overlay) => overlay.len,
+            None => self.base.count(table),
+        })
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(match self.table_overlay(table) {
+            Some(overlay) => overlay.get(&self.base, table, key),
+            None => self.base.get(table, key),
+        })
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        // the iterator holds a snapshot of the table at the time of the call
+        Ok(self.range(table, range, direction))
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        Ok(match self.multimap_overlay(table) {
+            Some(overlay) => overlay.len,
+            None => self.base.count_multimap(table),
+        })
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        Ok(self.range_multimap(table, key..=key, SortDirection::Asc))
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        Ok(self.range_multimap(table, range, direction))
+    }
+}
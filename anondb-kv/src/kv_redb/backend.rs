@@ -0,0 +1,38 @@
+use std::io;
+use std::sync::Arc;
+
+use redb::StorageBackend;
+use redb::backends::InMemoryBackend;
+
+/// An in memory redb backend that can be read after the database using it is dropped.
+#[derive(Debug, Clone, Default)]
+pub struct SharedMemoryBackend(Arc<InMemoryBackend>);
+
+impl SharedMemoryBackend {
+    /// Read the full contents of the backend.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+        self.0.read(0, self.0.len()? as usize)
+    }
+}
+
+impl StorageBackend for SharedMemoryBackend {
+    fn len(&self) -> Result<u64, io::Error> {
+        self.0.len()
+    }
+
+    fn read(&self, offset: u64, len: usize) -> Result<Vec<u8>, io::Error> {
+        self.0.read(offset, len)
+    }
+
+    fn set_len(&self, len: u64) -> Result<(), io::Error> {
+        self.0.set_len(len)
+    }
+
+    fn sync_data(&self, eventual: bool) -> Result<(), io::Error> {
+        self.0.sync_data(eventual)
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<(), io::Error> {
+        self.0.write(offset, data)
+    }
+}
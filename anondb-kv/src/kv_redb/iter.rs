@@ -1,7 +1,11 @@
+use std::collections::VecDeque;
+use std::ops::Bound;
 use std::sync::Arc;
 
 use anyhow::Result;
 
+use super::*;
+
 pub struct MaybeEmptyIter<I: Iterator> {
     inner_iter: Option<I>,
 }
@@ -65,19 +69,17 @@ impl<T, I: Iterator<Item = Result<V>>, V: Iterator<Item = Result<T>>> Iterator
             } else {
                 n
             }
-        } else {
-            if let Some(n) = self.inner_iter.next() {
-                match n {
-                    Ok(v) => {
-                        self.to_be_flattened = Some(v);
-                        self.next()
-                    }
-                    // this will loop infinitely?
-                    Err(e) => Some(Err(e)),
+        } else if let Some(n) = self.inner_iter.next() {
+            match n {
+                Ok(v) => {
+                    self.to_be_flattened = Some(v);
+                    self.next()
                 }
-            } else {
-                None
+                // this will loop infinitely?
+                Err(e) => Some(Err(e)),
             }
+        } else {
+            None
         }
     }
 }
@@ -92,3 +94,129 @@ impl<T, I: Iterator<Item = Result<V>>, V: Iterator<Item = Result<T>>> From<I>
         }
     }
 }
+
+/// Iterates over a range of a table in a write transaction. Tables opened in a redb write
+/// transaction borrow it, so the table is opened on each call to `next` and the iterator seeks past
+/// the previously returned key. Writes made to the transaction between calls are visible.
+pub struct RedbWriteRangeIter<'a> {
+    pub tx: &'a RedbWriteTransaction,
+    pub table: String,
+    pub start: Bound<Vec<u8>>,
+    pub end: Bound<Vec<u8>>,
+    pub direction: SortDirection,
+    pub done: bool,
+}
+
+impl RedbWriteRangeIter<'_> {
+    fn next_item(&mut self) -> Result<Option<RedbItem<'static>>> {
+        if !is_valid_range(&self.start, &self.end) {
+            return Ok(None);
+        }
+        let table = self.tx.write.open_table(tabledef(&self.table))?;
+        let mut range =
+            table.range::<&[u8]>((as_slice_bound(&self.start), as_slice_bound(&self.end)))?;
+        let item = match self.direction {
+            SortDirection::Asc => range.next(),
+            SortDirection::Desc => range.next_back(),
+        };
+        let Some(item) = item else {
+            return Ok(None);
+        };
+        let (key, value) = item?;
+        let key = key.value().to_vec();
+        match self.direction {
+            SortDirection::Asc => self.start = Bound::Excluded(key.clone()),
+            SortDirection::Desc => self.end = Bound::Excluded(key.clone()),
+        }
+        Ok(Some(RedbItem {
+            item: (key.into(), value.value().to_vec().into()),
+        }))
+    }
+}
+
+impl Iterator for RedbWriteRangeIter<'_> {
+    type Item = Result<RedbItem<'static>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.next_item().transpose();
+        self.done = !matches!(item, Some(Ok(_)));
+        item
+    }
+}
+
+/// Iterates over a range of keys in a multimap table in a write transaction. Like
+/// `RedbWriteRangeIter` the table is opened on each call to `next`. redb cannot seek within the
+/// values of a key, so the values of each key are read together when the iterator reaches it.
+pub struct RedbWriteMultimapIter<'a> {
+    pub tx: &'a RedbWriteTransaction,
+    pub table: String,
+    pub start: Bound<Vec<u8>>,
+    pub end: Bound<Vec<u8>>,
+    pub direction: SortDirection,
+    /// The current key.
+    pub key: Arc<Vec<u8>>,
+    /// Values of the current key that have not been returned, in iteration order.
+    pub values: VecDeque<Vec<u8>>,
+    pub done: bool,
+}
+
+impl RedbWriteMultimapIter<'_> {
+    /// Read the values of the next key in the range. Returns `false` if no keys remain.
+    fn next_key(&mut self) -> Result<bool> {
+        if !is_valid_range(&self.start, &self.end) {
+            return Ok(false);
+        }
+        let table = self
+            .tx
+            .write
+            .open_multimap_table(tabledef_multimap(&self.table))?;
+        let mut range =
+            table.range::<&[u8]>((as_slice_bound(&self.start), as_slice_bound(&self.end)))?;
+        let item = match self.direction {
+            SortDirection::Asc => range.next(),
+            SortDirection::Desc => range.next_back(),
+        };
+        let Some(item) = item else {
+            return Ok(false);
+        };
+        let (key, values) = item?;
+        let key = key.value().to_vec();
+        let values = DirectedIter::new(values, self.direction)
+            .map(|value| Ok(value?.value().to_vec()))
+            .collect::<Result<VecDeque<_>>>()?;
+        match self.direction {
+            SortDirection::Asc => self.start = Bound::Excluded(key.clone()),
+            SortDirection::Desc => self.end = Bound::Excluded(key.clone()),
+        }
+        self.key = Arc::new(key);
+        self.values = values;
+        Ok(true)
+    }
+
+    fn next_item(&mut self) -> Result<Option<RedbItem<'static>>> {
+        loop {
+            if let Some(value) = self.values.pop_front() {
+                return Ok(Some(RedbItem {
+                    item: (self.key.clone().into(), value.into()),
+                }));
+            }
+            if !self.next_key()? {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl Iterator for RedbWriteMultimapIter<'_> {
+    type Item = Result<RedbItem<'static>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let item = self.next_item().transpose();
+        self.done = !matches!(item, Some(Ok(_)));
+        item
+    }
+}
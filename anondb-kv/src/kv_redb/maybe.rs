@@ -9,7 +9,7 @@ pub struct RedbItem<'a> {
     pub item: (MaybeGuarded<'a>, MaybeGuarded<'a>),
 }
 
-impl<'a> OpaqueItem for RedbItem<'a> {
+impl OpaqueItem for RedbItem<'_> {
     fn key(&self) -> &[u8] {
         self.item.0.value()
     }
@@ -26,7 +26,7 @@ pub enum MaybeOwned<'a, T> {
     Arc(Arc<T>),
 }
 
-impl<'a, T> MaybeOwned<'a, T> {
+impl<T> MaybeOwned<'_, T> {
     pub fn as_ref(&self) -> &T {
         match self {
             MaybeOwned::Borrowed(r) => r,
@@ -36,7 +36,7 @@ impl<'a, T> MaybeOwned<'a, T> {
     }
 }
 
-impl<'a, T> From<T> for MaybeOwned<'a, T> {
+impl<T> From<T> for MaybeOwned<'_, T> {
     fn from(value: T) -> Self {
         MaybeOwned::Owned(value)
     }
@@ -48,7 +48,7 @@ impl<'a, T> From<&'a T> for MaybeOwned<'a, T> {
     }
 }
 
-impl<'a, T> From<Arc<T>> for MaybeOwned<'a, T> {
+impl<T> From<Arc<T>> for MaybeOwned<'_, T> {
     fn from(value: Arc<T>) -> Self {
         MaybeOwned::Arc(value)
     }
@@ -62,7 +62,7 @@ pub enum MaybeGuarded<'a> {
     Arc(Arc<Vec<u8>>),
 }
 
-impl<'a> MaybeGuarded<'a> {
+impl MaybeGuarded<'_> {
     pub fn value(&self) -> &[u8] {
         match self {
             MaybeGuarded::Guarded(v) => v.as_ref().value(),
@@ -79,13 +79,13 @@ impl<'a> From<Arc<AccessGuard<'a, &'static [u8]>>> for MaybeGuarded<'a> {
     }
 }
 
-impl<'a> From<Vec<u8>> for MaybeGuarded<'a> {
+impl From<Vec<u8>> for MaybeGuarded<'_> {
     fn from(value: Vec<u8>) -> Self {
         MaybeGuarded::Owned(value)
     }
 }
 
-impl<'a> From<Arc<Vec<u8>>> for MaybeGuarded<'a> {
+impl From<Arc<Vec<u8>>> for MaybeGuarded<'_> {
     fn from(value: Arc<Vec<u8>>) -> Self {
         MaybeGuarded::Arc(value)
     }
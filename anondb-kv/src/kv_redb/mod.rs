@@ -1,7 +1,9 @@
+mod backend;
 mod iter;
 mod maybe;
 mod tx;
 
+use backend::*;
 use iter::*;
 use maybe::*;
 use tx::*;
@@ -17,6 +19,8 @@ use redb::*;
 
 use super::*;
 
+pub use redb::Durability;
+
 fn tabledef(name: &str) -> TableDefinition<&'static [u8], &'static [u8]> {
     TableDefinition::new(name)
 }
@@ -25,29 +29,113 @@ fn tabledef_multimap(name: &str) -> MultimapTableDefinition<&'static [u8], &'sta
     MultimapTableDefinition::new(name)
 }
 
+/// Options for opening a file backed redb database.
+#[derive(Debug, Clone, Copy)]
+pub struct RedbOptions {
+    /// Create the database file if it does not exist. If `false` the file must already contain a
+    /// redb database.
+    pub create: bool,
+    /// Reject all write transactions. The database file must already exist. The file is still
+    /// opened with an exclusive lock, so other processes cannot open it at the same time.
+    pub reject_writes: bool,
+    /// Size of the redb page cache in bytes. `None` uses the redb default.
+    pub cache_size: Option<usize>,
+    /// Durability applied to every write transaction created by the kv.
+    pub durability: Durability,
+}
+
+impl Default for RedbOptions {
+    fn default() -> Self {
+        Self {
+            create: true,
+            reject_writes: false,
+            cache_size: None,
+            durability: Durability::Immediate,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RedbKV {
     db: Database,
+    reject_writes: bool,
+    durability: Durability,
+}
+
+impl RedbKV {
+    /// Open a redb database stored in a single file at `path` using the provided options.
+    pub fn at_path_with_options(path: &std::path::Path, options: RedbOptions) -> Result<Self> {
+        let mut builder = Database::builder();
+        if let Some(cache_size) = options.cache_size {
+            builder.set_cache_size(cache_size);
+        }
+        let db = if options.create && !options.reject_writes {
+            builder.create(path)?
+        } else {
+            if !path.exists() {
+                anyhow::bail!("RedbKV: no database exists at path \"{}\"", path.display());
+            }
+            builder.open(path)?
+        };
+        Ok(Self {
+            db,
+            reject_writes: options.reject_writes,
+            durability: options.durability,
+        })
+    }
 }
 
 impl KV for RedbKV {
     type ReadTransaction = RedbReadTransaction;
     type WriteTransaction = RedbWriteTransaction;
 
-    fn at_path(_path: &std::path::Path) -> Result<Self> {
-        unimplemented!()
+    fn at_path(path: &std::path::Path) -> Result<Self> {
+        Self::at_path_with_options(path, RedbOptions::default())
     }
 
     fn in_memory(bytes_maybe: Option<&[u8]>) -> Result<Self> {
         let mem_backend = redb::backends::InMemoryBackend::new();
         if let Some(bytes) = bytes_maybe {
+            mem_backend.set_len(bytes.len() as u64)?;
             mem_backend.write(0, bytes)?;
         }
         Ok(Self {
             db: Database::builder().create_with_backend(mem_backend)?,
+            reject_writes: false,
+            durability: Durability::Immediate,
         })
     }
 
+    fn export_bytes(&self) -> Result<Vec<u8>> {
+        // copy the current state into a fresh in memory database and read the underlying bytes
+        let backend = SharedMemoryBackend::default();
+        {
+            let export = Database::builder().create_with_backend(backend.clone())?;
+            let read = self.db.begin_read()?;
+            let write = export.begin_write()?;
+            for handle in read.list_tables()? {
+                let from = read.open_table(tabledef(handle.name()))?;
+                let mut to = write.open_table(tabledef(handle.name()))?;
+                for item in from.iter()? {
+                    let (k, v) = item?;
+                    to.insert(k.value(), v.value())?;
+                }
+            }
+            for handle in read.list_multimap_tables()? {
+                let from = read.open_multimap_table(tabledef_multimap(handle.name()))?;
+                let mut to = write.open_multimap_table(tabledef_multimap(handle.name()))?;
+                for item in from.iter()? {
+                    let (k, values) = item?;
+                    for v in values {
+                        to.insert(k.value(), v?.value())?;
+                    }
+                }
+            }
+            write.commit()?;
+        }
+        Ok(backend.to_bytes()?)
+    }
+
     fn scan<S>(&self, table: &str, predicate: S) -> Result<()>
     where
         S: Fn(&[u8], &[u8]) -> Result<bool>,
@@ -62,9 +150,12 @@ impl KV for RedbKV {
     }
 
     fn write_tx(&self) -> Result<Self::WriteTransaction> {
-        Ok(RedbWriteTransaction {
-            write: self.db.begin_write()?,
-        })
+        if self.reject_writes {
+            anyhow::bail!("RedbKV: cannot begin a write transaction, writes are rejected");
+        }
+        let mut write = self.db.begin_write()?;
+        write.set_durability(self.durability);
+        Ok(RedbWriteTransaction { write })
     }
 
     fn read_tx(&self) -> Result<Self::ReadTransaction> {
@@ -117,17 +208,18 @@ impl ReadOperations for RedbKV {
         tx.get(table, key)
     }
 
-    fn range<'a>(
+    fn range_directed<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
         let tx: <Self as KV>::ReadTransaction = self.read_tx()?;
-        let table = match tx.read_table(&table)? {
+        let table = match tx.read_table(table)? {
             Some(t) => t,
             None => return Ok(MaybeEmptyIter::default()),
         };
-        let inner_iter = table.range(range)?;
+        let inner_iter = DirectedIter::new(table.range(range)?, direction);
         Ok(RedbReadIter {
             data: Arc::new(()),
             inner_iter,
@@ -141,21 +233,24 @@ impl ReadOperations for RedbKV {
         .into())
     }
 
-    fn range_multimap<'a>(
+    fn range_multimap_directed<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
         let tx: <Self as KV>::ReadTransaction = self.read_tx()?;
-        let table = match tx.read_multimap_table(&table)? {
+        let table = match tx.read_multimap_table(table)? {
             Some(t) => t,
             None => return Ok(MaybeEmptyIter::default()),
         };
-        let inner_iter = FlatMapFallible::from(table.range(range)?.map(|v| {
-            let (key, vals) = v?;
-            let key = Arc::new(key);
-            Ok(vals.map(move |v| Ok((key.clone(), v?))))
-        }));
+        let inner_iter = FlatMapFallible::from(
+            DirectedIter::new(table.range(range)?, direction).map(move |v| {
+                let (key, vals) = v?;
+                let key = Arc::new(key);
+                Ok(DirectedIter::new(vals, direction).map(move |v| Ok((key.clone(), v?))))
+            }),
+        );
         Ok(RedbReadIter {
             data: Arc::new(()),
             inner_iter,
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::ops::Bound;
 use std::ops::RangeBounds;
 use std::sync::Arc;
 use std::sync::RwLock;
@@ -9,11 +11,13 @@ use redb::*;
 
 use super::*;
 
+type BytesTable = ReadOnlyTable<&'static [u8], &'static [u8]>;
+type BytesMultimapTable = ReadOnlyMultimapTable<&'static [u8], &'static [u8]>;
+
 pub struct RedbReadTransaction {
     pub read: redb::ReadTransaction,
-    pub tables: RwLock<HashMap<String, Arc<ReadOnlyTable<&'static [u8], &'static [u8]>>>>,
-    pub multimap_tables:
-        RwLock<HashMap<String, Arc<ReadOnlyMultimapTable<&'static [u8], &'static [u8]>>>>,
+    pub tables: RwLock<HashMap<String, Arc<BytesTable>>>,
+    pub multimap_tables: RwLock<HashMap<String, Arc<BytesMultimapTable>>>,
 }
 
 pub struct RedbWriteTransaction {
@@ -21,10 +25,7 @@ pub struct RedbWriteTransaction {
 }
 
 impl RedbReadTransaction {
-    pub fn read_table(
-        &self,
-        name: &str,
-    ) -> Result<Option<Arc<ReadOnlyTable<&'static [u8], &'static [u8]>>>> {
+    pub fn read_table(&self, name: &str) -> Result<Option<Arc<BytesTable>>> {
         if let Some(table) = self.tables.read().unwrap().get(name) {
             return Ok(Some(table.clone()));
         }
@@ -46,10 +47,7 @@ impl RedbReadTransaction {
         Ok(Some(table))
     }
 
-    pub fn read_multimap_table(
-        &self,
-        name: &str,
-    ) -> Result<Option<Arc<ReadOnlyMultimapTable<&'static [u8], &'static [u8]>>>> {
+    pub fn read_multimap_table(&self, name: &str) -> Result<Option<Arc<BytesMultimapTable>>> {
         if let Some(table) = self.multimap_tables.read().unwrap().get(name) {
             return Ok(Some(table.clone()));
         }
@@ -72,6 +70,63 @@ impl RedbReadTransaction {
     }
 }
 
+impl ReadTx for RedbReadTransaction {
+    type Item = RedbItem<'static>;
+
+    fn range_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        let table = match self.read_table(table)? {
+            Some(t) => t,
+            None => return Ok(Box::new(std::iter::empty())),
+        };
+        let range = (
+            range.0.as_ref().map(|v| v.as_slice()),
+            range.1.as_ref().map(|v| v.as_slice()),
+        );
+        // redb read only tables return iterators that keep the transaction alive
+        let inner_iter = DirectedIter::new(table.range::<&[u8]>(range)?, direction);
+        Ok(Box::new(inner_iter.map(|item| {
+            let (k, v) = item?;
+            Ok(RedbItem {
+                item: (k.into(), v.into()),
+            })
+        })))
+    }
+
+    fn range_multimap_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        let table = match self.read_multimap_table(table)? {
+            Some(t) => t,
+            None => return Ok(Box::new(std::iter::empty())),
+        };
+        let range = (
+            range.0.as_ref().map(|v| v.as_slice()),
+            range.1.as_ref().map(|v| v.as_slice()),
+        );
+        let inner_iter = FlatMapFallible::from(
+            DirectedIter::new(table.range::<&[u8]>(range)?, direction).map(move |v| {
+                let (key, vals) = v?;
+                let key = Arc::new(key);
+                Ok(DirectedIter::new(vals, direction).map(move |v| Ok((key.clone(), v?))))
+            }),
+        );
+        Ok(Box::new(inner_iter.map(|item| {
+            let (k, v) = item?;
+            Ok(RedbItem {
+                item: (k.into(), v.into()),
+            })
+        })))
+    }
+}
+
 impl WriteTx for RedbWriteTransaction {
     fn commit(self) -> Result<()> {
         self.write.commit()?;
@@ -164,16 +219,17 @@ impl ReadOperations for RedbReadTransaction {
         }
     }
 
-    fn range<'a>(
+    fn range_directed<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
-        let table = match self.read_table(&table)? {
+        let table = match self.read_table(table)? {
             Some(t) => t,
             None => return Ok(MaybeEmptyIter::default()),
         };
-        let inner_iter = table.range(range)?;
+        let inner_iter = DirectedIter::new(table.range(range)?, direction);
         Ok(inner_iter
             .map(|item| {
                 let (k, v) = item?;
@@ -184,20 +240,23 @@ impl ReadOperations for RedbReadTransaction {
             .into())
     }
 
-    fn range_multimap<'a>(
+    fn range_multimap_directed<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]>,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
-        let table = match self.read_multimap_table(&table)? {
+        let table = match self.read_multimap_table(table)? {
             Some(t) => t,
             None => return Ok(MaybeEmptyIter::default()),
         };
-        let inner_iter = FlatMapFallible::from(table.range(range)?.map(|v| {
-            let (key, vals) = v?;
-            let key = Arc::new(key);
-            Ok(vals.map(move |v| Ok((key.clone(), v?))))
-        }));
+        let inner_iter = FlatMapFallible::from(
+            DirectedIter::new(table.range(range)?, direction).map(move |v| {
+                let (key, vals) = v?;
+                let key = Arc::new(key);
+                Ok(DirectedIter::new(vals, direction).map(move |v| Ok((key.clone(), v?))))
+            }),
+        );
         Ok(inner_iter
             .map(|item| {
                 let (k, v) = item?;
@@ -219,9 +278,9 @@ impl ReadOperations for RedbWriteTransaction {
         // this is because of limitations with lifetimes in redb transactions
         let key = Arc::new(key.to_vec());
         let table = self.write.open_multimap_table(tabledef_multimap(table))?;
-        let mut entry = table.get(key.as_slice())?;
+        let entry = table.get(key.as_slice())?;
         let mut out = Vec::default();
-        while let Some(item) = entry.next() {
+        for item in entry {
             let val = item?;
             out.push(Ok(RedbItem {
                 item: (key.clone().into(), val.value().to_vec().into()),
@@ -245,46 +304,37 @@ impl ReadOperations for RedbWriteTransaction {
         Ok(table.len()?)
     }
 
-    fn range<'a>(
+    fn range_directed<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
-        // this implementation allocates all items from the range into memory
-        // this is because of limitations with lifetimes in redb transactions
-        let table = self.write.open_table(tabledef(&table))?;
-        let mut entry = table.range(range)?;
-        let mut out = Vec::default();
-        while let Some(item) = entry.next() {
-            let (key, val) = item?;
-            out.push(Ok(RedbItem {
-                item: (key.value().to_vec().into(), val.value().to_vec().into()),
-            }));
-        }
-        drop(table);
-        Ok(out.into_iter())
+        Ok(RedbWriteRangeIter {
+            tx: self,
+            table: table.to_string(),
+            start: range.start_bound().map(|v| v.to_vec()),
+            end: range.end_bound().map(|v| v.to_vec()),
+            direction,
+            done: false,
+        })
     }
 
-    fn range_multimap<'a>(
+    fn range_multimap_directed<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
-        // this implementation allocates all items from the range into memory
-        // this is because of limitations with lifetimes in redb transactions
-        let table = self.write.open_multimap_table(tabledef_multimap(&table))?;
-        let mut entry = table.range(range)?;
-        let mut out = Vec::default();
-        while let Some(item) = entry.next() {
-            let (key, mut values) = item?;
-            let key = Arc::new(key.value().to_vec());
-            while let Some(val) = values.next() {
-                let val = val?;
-                out.push(Ok(RedbItem {
-                    item: (key.clone().into(), val.value().to_vec().into()),
-                }));
-            }
-        }
-        Ok(out.into_iter())
+        Ok(RedbWriteMultimapIter {
+            tx: self,
+            table: table.to_string(),
+            start: range.start_bound().map(|v| v.to_vec()),
+            end: range.end_bound().map(|v| v.to_vec()),
+            direction,
+            key: Arc::default(),
+            values: VecDeque::default(),
+            done: false,
+        })
     }
 }
@@ -58,79 +58,62 @@ impl SerializeLexicographic for () {
 impl<T: SerializeLexicographic> SerializeLexicographic for Option<T> {
     fn serialize_lex(&self) -> Vec<u8> {
         match self {
-            Some(v) => vec![vec![0x01], SerializeLexicographic::serialize_lex(v)].concat(),
+            Some(v) => [vec![0x01], SerializeLexicographic::serialize_lex(v)].concat(),
             None => vec![0x00],
         }
     }
 
     fn min() -> Vec<u8> {
-        vec![vec![0x00], T::min()].concat()
+        [vec![0x00], T::min()].concat()
     }
 
     fn max() -> Option<Vec<u8>> {
-        T::max().map(|v| vec![vec![0x01], v].concat())
+        T::max().map(|v| [vec![0x01], v].concat())
     }
 
-    fn fixed_width() -> Option<u32> {
-        T::fixed_width().map(|v| v + 1)
-    }
+    // `None` is a single byte and `Some` is longer, so the width is never fixed
 }
 
-impl SerializeLexicographic for String {
-    fn serialize_lex(&self) -> Vec<u8> {
-        vec![self.as_bytes().to_vec(), vec![0x00]].concat()
-    }
-
-    fn min() -> Vec<u8> {
-        vec![0x00]
-    }
-
-    fn max() -> Option<Vec<u8>> {
-        None
-    }
-}
-
-impl SerializeLexicographic for &String {
-    fn serialize_lex(&self) -> Vec<u8> {
-        vec![self.as_bytes().to_vec(), vec![0x00]].concat()
-    }
-
-    fn min() -> Vec<u8> {
-        vec![0x00]
-    }
-
-    fn max() -> Option<Vec<u8>> {
-        None
+/// Escape a variable length byte string so it sorts correctly when followed by other keys. Each
+/// 0x00 byte is written as 0x00 0xFF, and the string is terminated by 0x00 0x00. The terminator
+/// sorts before any continuation, so a string sorts before every longer string it prefixes.
+pub fn serialize_lex_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len() + 2);
+    for byte in bytes {
+        out.push(*byte);
+        if *byte == 0x00 {
+            out.push(0xFF);
+        }
     }
+    out.extend_from_slice(&[0x00, 0x00]);
+    out
 }
 
-impl SerializeLexicographic for &str {
-    fn serialize_lex(&self) -> Vec<u8> {
-        vec![self.as_bytes().to_vec(), vec![0x00]].concat()
-    }
+macro_rules! lex_bytes {
+    ($type:ty, $as_bytes:ident) => {
+        impl SerializeLexicographic for $type {
+            fn serialize_lex(&self) -> Vec<u8> {
+                serialize_lex_bytes(self.$as_bytes())
+            }
 
-    fn min() -> Vec<u8> {
-        vec![0x00]
-    }
+            fn min() -> Vec<u8> {
+                vec![0x00, 0x00]
+            }
 
-    fn max() -> Option<Vec<u8>> {
-        None
-    }
+            fn max() -> Option<Vec<u8>> {
+                None
+            }
+        }
+    };
 }
 
-impl SerializeLexicographic for &&str {
-    fn serialize_lex(&self) -> Vec<u8> {
-        vec![self.as_bytes().to_vec(), vec![0x00]].concat()
-    }
-
-    fn min() -> Vec<u8> {
-        vec![0x00]
-    }
-
-    fn max() -> Option<Vec<u8>> {
-        None
-    }
-}
+lex_bytes!(String, as_bytes);
+lex_bytes!(&String, as_bytes);
+lex_bytes!(&str, as_bytes);
+lex_bytes!(&&str, as_bytes);
+lex_bytes!(Vec<u8>, as_slice);
+lex_bytes!(&Vec<u8>, as_slice);
+lex_bytes!(&[u8], as_ref);
 
 impl SerializeLexicographic for bool {
     fn serialize_lex(&self) -> Vec<u8> {
@@ -192,6 +175,196 @@ lex_uint!(u32);
 lex_uint!(u64);
 lex_uint!(u128);
 
+/// Signed integers are encoded big endian with the sign bit flipped, so negative values sort
+/// before positive values.
+macro_rules! lex_int {
+    ($int:ident, $uint:ident) => {
+        impl SerializeLexicographic for &$int {
+            fn serialize_lex(&self) -> Vec<u8> {
+                (**self).serialize_lex()
+            }
+
+            fn min() -> Vec<u8> {
+                <$int as SerializeLexicographic>::min()
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                <$int as SerializeLexicographic>::max()
+            }
+
+            fn fixed_width() -> Option<u32> {
+                <$int as SerializeLexicographic>::fixed_width()
+            }
+        }
+
+        impl SerializeLexicographic for $int {
+            fn serialize_lex(&self) -> Vec<u8> {
+                ((*self as $uint) ^ (1 << ($int::BITS - 1)))
+                    .to_be_bytes()
+                    .to_vec()
+            }
+
+            fn min() -> Vec<u8> {
+                $uint::MIN.to_be_bytes().to_vec()
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                Some($uint::MAX.to_be_bytes().to_vec())
+            }
+
+            fn fixed_width() -> Option<u32> {
+                Some($int::BITS / 8)
+            }
+        }
+    };
+}
+
+lex_int!(i8, u8);
+lex_int!(i16, u16);
+lex_int!(i32, u32);
+lex_int!(i64, u64);
+lex_int!(i128, u128);
+
+/// Pointer sized integers are encoded as 64 bit integers so keys are the same on every platform.
+macro_rules! lex_size {
+    ($size:ident, $int:ident) => {
+        impl SerializeLexicographic for &$size {
+            fn serialize_lex(&self) -> Vec<u8> {
+                (**self).serialize_lex()
+            }
+
+            fn min() -> Vec<u8> {
+                <$int as SerializeLexicographic>::min()
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                <$int as SerializeLexicographic>::max()
+            }
+
+            fn fixed_width() -> Option<u32> {
+                <$int as SerializeLexicographic>::fixed_width()
+            }
+        }
+
+        impl SerializeLexicographic for $size {
+            fn serialize_lex(&self) -> Vec<u8> {
+                (*self as $int).serialize_lex()
+            }
+
+            fn min() -> Vec<u8> {
+                <$int as SerializeLexicographic>::min()
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                <$int as SerializeLexicographic>::max()
+            }
+
+            fn fixed_width() -> Option<u32> {
+                <$int as SerializeLexicographic>::fixed_width()
+            }
+        }
+    };
+}
+
+lex_size!(usize, u64);
+lex_size!(isize, i64);
+
+/// Floats are encoded with a total order: all bits are flipped for negative values, and the sign
+/// bit is flipped for positive values. `-0.0` is encoded as `0.0` so keys agree with `==`.
+///
+/// NaN values are ordered by their bits. A NaN with the sign bit set sorts before negative
+/// infinity, otherwise after positive infinity. Because NaN is not equal to itself, documents
+/// with NaN fields never match a query, even if the index contains them.
+macro_rules! lex_float {
+    ($float:ident, $uint:ident) => {
+        impl SerializeLexicographic for &$float {
+            fn serialize_lex(&self) -> Vec<u8> {
+                (**self).serialize_lex()
+            }
+
+            fn min() -> Vec<u8> {
+                <$float as SerializeLexicographic>::min()
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                <$float as SerializeLexicographic>::max()
+            }
+
+            fn fixed_width() -> Option<u32> {
+                <$float as SerializeLexicographic>::fixed_width()
+            }
+        }
+
+        impl SerializeLexicographic for $float {
+            fn serialize_lex(&self) -> Vec<u8> {
+                let sign_bit = 1 << ($uint::BITS - 1);
+                let bits = if *self == 0.0 { 0 } else { self.to_bits() };
+                let bits = if bits & sign_bit == 0 {
+                    bits ^ sign_bit
+                } else {
+                    !bits
+                };
+                bits.to_be_bytes().to_vec()
+            }
+
+            fn min() -> Vec<u8> {
+                $uint::MIN.to_be_bytes().to_vec()
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                Some($uint::MAX.to_be_bytes().to_vec())
+            }
+
+            fn fixed_width() -> Option<u32> {
+                Some($uint::BITS / 8)
+            }
+        }
+    };
+}
+
+lex_float!(f32, u32);
+lex_float!(f64, u64);
+
+/// Tuples are encoded element by element. Every encoding is self delimiting, so the
+/// concatenation sorts the same as the tuple. The width is fixed only if every element has a
+/// fixed width.
+macro_rules! lex_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: SerializeLexicographic),+> SerializeLexicographic for ($($name,)+) {
+            fn serialize_lex(&self) -> Vec<u8> {
+                let mut out = Vec::default();
+                $(out.extend(self.$index.serialize_lex());)+
+                out
+            }
+
+            fn min() -> Vec<u8> {
+                let mut out = Vec::default();
+                $(out.extend($name::min());)+
+                out
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                let mut out = Vec::default();
+                $(out.extend($name::max()?);)+
+                Some(out)
+            }
+
+            fn fixed_width() -> Option<u32> {
+                Some(0 $(+ $name::fixed_width()?)+)
+            }
+        }
+    };
+}
+
+lex_tuple!(A 0);
+lex_tuple!(A 0, B 1);
+lex_tuple!(A 0, B 1, C 2);
+lex_tuple!(A 0, B 1, C 2, D 3);
+lex_tuple!(A 0, B 1, C 2, D 3, E 4);
+lex_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+lex_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+lex_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
 pub struct AssertSize<const N: usize>;
 
 impl<const N: usize> AssertSize<N> {
@@ -204,7 +377,7 @@ impl<const N: usize> AssertSize<N> {
 impl<const N: usize> SerializeLexicographic for [u8; N] {
     fn serialize_lex(&self) -> Vec<u8> {
         // compile time assertion that N fits in a u32
-        let _ = AssertSize::<N>::OK;
+        let () = AssertSize::<N>::OK;
         self.to_vec()
     }
 
@@ -225,6 +398,165 @@ impl<const N: usize> SerializeLexicographic for [u8; N] {
 mod test {
     use crate::*;
 
+    #[test]
+    fn should_sort_signed_ints() {
+        let mut values = vec![
+            i64::MIN,
+            i64::MIN + 1,
+            -256,
+            -1,
+            0,
+            1,
+            255,
+            i64::MAX - 1,
+            i64::MAX,
+        ];
+        values.extend((0..100).map(|_| rand::random::<i64>()));
+        for v0 in &values {
+            for v1 in &values {
+                assert_eq!(v0.serialize_lex().cmp(&v1.serialize_lex()), v0.cmp(v1));
+            }
+        }
+        for _ in 0..100 {
+            let v0 = rand::random::<i8>();
+            let v1 = rand::random::<i8>();
+            assert_eq!(v0.serialize_lex().cmp(&v1.serialize_lex()), v0.cmp(&v1));
+        }
+        assert_eq!(
+            i32::MIN.serialize_lex(),
+            <i32 as SerializeLexicographic>::min()
+        );
+        assert_eq!(
+            Some(i32::MAX.serialize_lex()),
+            <i32 as SerializeLexicographic>::max()
+        );
+        assert_eq!(i128::fixed_width(), Some(16));
+        assert_eq!((-1isize).serialize_lex(), (-1i64).serialize_lex());
+        assert_eq!(usize::fixed_width(), Some(8));
+    }
+
+    #[test]
+    fn should_sort_floats() {
+        let mut values = vec![
+            f64::NEG_INFINITY,
+            f64::MIN,
+            -1.5,
+            -f64::MIN_POSITIVE,
+            0.0,
+            f64::MIN_POSITIVE,
+            1.5,
+            f64::MAX,
+            f64::INFINITY,
+        ];
+        values.extend((0..100).map(|_| (rand::random::<f64>() - 0.5) * 1e6));
+        for v0 in &values {
+            for v1 in &values {
+                assert_eq!(
+                    v0.serialize_lex().cmp(&v1.serialize_lex()),
+                    v0.total_cmp(v1)
+                );
+            }
+        }
+        for _ in 0..100 {
+            let v0 = f32::from_bits(rand::random());
+            let v1 = f32::from_bits(rand::random());
+            if v0.is_nan() || v1.is_nan() || v0 == 0.0 || v1 == 0.0 {
+                continue;
+            }
+            assert_eq!(
+                v0.serialize_lex().cmp(&v1.serialize_lex()),
+                v0.total_cmp(&v1)
+            );
+        }
+        assert_eq!((-0.0f64).serialize_lex(), 0.0f64.serialize_lex());
+        // NaN sorts outside of the infinities
+        assert!(f32::NAN.serialize_lex() > f32::INFINITY.serialize_lex());
+        assert!((-f32::NAN).serialize_lex() < f32::NEG_INFINITY.serialize_lex());
+        assert_eq!(f32::fixed_width(), Some(4));
+    }
+
+    /// Random bytes drawn mostly from the values that matter to the escaping scheme.
+    fn rand_bytes() -> Vec<u8> {
+        let len = rand::random::<u8>() % 8;
+        (0..len)
+            .map(|_| match rand::random::<u8>() % 4 {
+                0 => 0x00,
+                1 => 0x01,
+                2 => 0xFF,
+                _ => rand::random(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_sort_bytes() {
+        for _ in 0..10_000 {
+            let b0 = rand_bytes();
+            let b1 = rand_bytes();
+            assert_eq!(b0.serialize_lex().cmp(&b1.serialize_lex()), b0.cmp(&b1));
+            assert_eq!(
+                b0.as_slice().serialize_lex(),
+                b0.serialize_lex(),
+                "slices and vectors should encode the same"
+            );
+
+            // order is preserved when followed by another key
+            let suffix0 = rand_bytes();
+            let suffix1 = rand_bytes();
+            let mut k0 = LexicographicKey::default();
+            k0.append_key_slice(&b0.serialize_lex());
+            k0.append_key_slice(&suffix0.serialize_lex());
+            let mut k1 = LexicographicKey::default();
+            k1.append_key_slice(&b1.serialize_lex());
+            k1.append_key_slice(&suffix1.serialize_lex());
+            assert_eq!(
+                k0.as_slice().cmp(k1.as_slice()),
+                (&b0, &suffix0).cmp(&(&b1, &suffix1))
+            );
+        }
+    }
+
+    #[test]
+    fn should_escape_nul() {
+        let a = "a".serialize_lex();
+        let a_nul_b = "a\0b".serialize_lex();
+        assert_eq!(a, vec![b'a', 0x00, 0x00]);
+        assert_eq!(a_nul_b, vec![b'a', 0x00, 0xFF, b'b', 0x00, 0x00]);
+        assert!(a < a_nul_b);
+        assert_eq!(
+            String::new().serialize_lex(),
+            <String as SerializeLexicographic>::min()
+        );
+    }
+
+    #[test]
+    fn should_sort_tuples() {
+        let values = (0..100)
+            .map(|_| {
+                (
+                    rand::random::<u32>() % 4,
+                    rand_bytes(),
+                    rand::random::<bool>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        for v0 in &values {
+            for v1 in &values {
+                assert_eq!(v0.serialize_lex().cmp(&v1.serialize_lex()), v0.cmp(v1));
+            }
+        }
+        assert_eq!(<(u32, u32)>::fixed_width(), Some(8));
+        assert_eq!(<(u32, String)>::fixed_width(), None);
+        assert_eq!(<(u32, Option<u32>)>::fixed_width(), None);
+        assert_eq!(<(u32, String) as SerializeLexicographic>::max(), None);
+        assert_eq!(<(u8, u16) as SerializeLexicographic>::min(), vec![0, 0, 0]);
+        assert_eq!(
+            <(u8, u16) as SerializeLexicographic>::max(),
+            Some(vec![0xFF; 3])
+        );
+        assert_eq!((1u32, 2u32).serialize_lex(), vec![0, 0, 0, 1, 0, 0, 0, 2]);
+    }
+
     #[test]
     fn should_sort_strings() {
         // test variable length strings
@@ -1,17 +1,28 @@
+mod image;
+#[cfg(feature = "fjall")]
+mod kv_fjall;
+mod kv_mem;
 #[cfg(feature = "redb")]
 mod kv_redb;
 mod lexicographic;
 mod sort;
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod test;
 
+pub use image::*;
+#[cfg(feature = "fjall")]
+pub use kv_fjall::*;
+pub use kv_mem::*;
 #[cfg(feature = "redb")]
 pub use kv_redb::*;
 pub use lexicographic::*;
 pub use sort::*;
 
+use std::ops::Bound;
 use std::ops::RangeBounds;
+use std::sync::Arc;
 
 use anyhow::Result;
 use serde::Deserialize;
@@ -24,7 +35,7 @@ pub trait OpaqueItem {
 
 /// A generic key-value store. Assumed to be capable of transactional mutation of key-value collections.
 pub trait KV: Sized + ReadOperations + WriteOperations {
-    type ReadTransaction: ReadOperations;
+    type ReadTransaction: ReadTx;
     type WriteTransaction: WriteTx;
 
     /// Initialize a kv persisted to a path. What path is (directory, file, etc) is determined by
@@ -33,6 +44,9 @@ pub trait KV: Sized + ReadOperations + WriteOperations {
     /// Initialize a kv with a byte representation of the initial state. This byte
     /// representation is arbitrary to the concrete implementation.
     fn in_memory(bytes_maybe: Option<&[u8]>) -> Result<Self>;
+    /// Serialize the current state of the kv into a byte representation accepted by
+    /// `in_memory`. Reflects a consistent view of the kv at a single point in time.
+    fn export_bytes(&self) -> Result<Vec<u8>>;
     fn write_tx(&self) -> Result<Self::WriteTransaction>;
     fn read_tx(&self) -> Result<Self::ReadTransaction>;
 
@@ -48,11 +62,22 @@ pub trait ReadOperations {
     fn count(&self, table: &str) -> Result<u64>;
     /// Retrieve the value associated to a key for a table. Must be `O(1)`.
     fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
-    /// Retrieve an iterator over a range of keys. Returns a reference to each key and value.
+    /// Retrieve an iterator over a range of keys in ascending order. Returns a reference to each
+    /// key and value.
     fn range<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]> + 'a,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        self.range_directed(table, range, SortDirection::Asc)
+    }
+    /// Retrieve an iterator over a range of keys, starting from the lowest key for `Asc` or the
+    /// highest key for `Desc`. Must not read keys beyond those returned by the iterator.
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a>;
 
     /// Determine the number of keys present in a multimap table.
@@ -64,10 +89,22 @@ pub trait ReadOperations {
         key: &[u8],
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>>;
 
+    /// Retrieve an iterator over a range of keys in a multimap table in ascending order. Values
+    /// for each key are returned in ascending order.
     fn range_multimap<'a>(
         &'a self,
         table: &str,
         range: impl RangeBounds<&'a [u8]> + 'a,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        self.range_multimap_directed(table, range, SortDirection::Asc)
+    }
+    /// Retrieve an iterator over a range of keys in a multimap table. For `Desc` both keys and
+    /// the values for each key are returned in descending order.
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
     ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a>;
 
     fn range_buffered<'a, T: for<'de> Deserialize<'de>>(
@@ -134,10 +171,55 @@ pub trait WriteOperations {
     fn clear_multimap(&self, table: &str) -> Result<()>;
 }
 
+/// An iterator that does not borrow the transaction it was created from.
+pub type DetachedIter<I> = Box<dyn Iterator<Item = Result<I>>>;
+
+/// A read only transaction over a consistent snapshot of the kv.
+pub trait ReadTx: ReadOperations + 'static {
+    type Item: OpaqueItem + 'static;
+
+    /// Retrieve an iterator over a range of keys that does not borrow the transaction. The
+    /// iterator reads from the same snapshot as the transaction, and may be stored alongside it.
+    fn range_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>>;
+
+    /// Retrieve an iterator over a range of keys in a multimap table that does not borrow the
+    /// transaction.
+    fn range_multimap_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>>;
+}
+
+/// A transaction that may read and write. Reads see the uncommitted writes of the transaction.
+/// The transaction may be written to while iterators returned by it are in use. Writes to keys an
+/// iterator has not reached yet may or may not be returned by it.
 pub trait WriteTx: ReadOperations + WriteOperations {
     fn commit(self) -> Result<()>;
 }
 
+/// `BTreeMap::range` and the ranges of some kv implementations panic on inverted or
+/// empty-excluded ranges. Determine if a range may be passed safely.
+pub(crate) fn is_valid_range<T: Ord>(start: &Bound<T>, end: &Bound<T>) -> bool {
+    match (start, end) {
+        (Bound::Included(s), Bound::Included(e)) => s <= e,
+        (Bound::Included(s), Bound::Excluded(e))
+        | (Bound::Excluded(s), Bound::Included(e))
+        | (Bound::Excluded(s), Bound::Excluded(e)) => s < e,
+        _ => true,
+    }
+}
+
+pub(crate) fn as_slice_bound(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    bound.as_ref().map(|v| v.as_slice())
+}
+
 #[cfg(test)]
 pub fn rand_utf8(len: usize) -> String {
     vec![char::default(); len]
@@ -196,3 +278,66 @@ impl<T: KV> WriteOperations for T {
         Ok(())
     }
 }
+
+/// Shared read transactions, used to run many queries against the same snapshot.
+impl<T: ReadOperations> ReadOperations for Arc<T> {
+    fn count(&self, table: &str) -> Result<u64> {
+        (**self).count(table)
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        (**self).get(table, key)
+    }
+
+    fn range_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        (**self).range_directed(table, range, direction)
+    }
+
+    fn count_multimap(&self, table: &str) -> Result<u64> {
+        (**self).count_multimap(table)
+    }
+
+    fn get_multimap(
+        &self,
+        table: &str,
+        key: &[u8],
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>>> {
+        (**self).get_multimap(table, key)
+    }
+
+    fn range_multimap_directed<'a>(
+        &'a self,
+        table: &str,
+        range: impl RangeBounds<&'a [u8]> + 'a,
+        direction: SortDirection,
+    ) -> Result<impl Iterator<Item = Result<impl OpaqueItem>> + 'a> {
+        (**self).range_multimap_directed(table, range, direction)
+    }
+}
+
+impl<T: ReadTx> ReadTx for Arc<T> {
+    type Item = T::Item;
+
+    fn range_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        (**self).range_detached(table, range, direction)
+    }
+
+    fn range_multimap_detached(
+        &self,
+        table: &str,
+        range: (Bound<Vec<u8>>, Bound<Vec<u8>>),
+        direction: SortDirection,
+    ) -> Result<DetachedIter<Self::Item>> {
+        (**self).range_multimap_detached(table, range, direction)
+    }
+}
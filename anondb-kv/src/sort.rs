@@ -1,15 +1,40 @@
-#[derive(Debug, Clone, Default, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum SortDirection {
     #[default]
     Asc,
     Desc,
 }
 
-impl ToString for SortDirection {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for SortDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Asc => "asc".into(),
-            Self::Desc => "desc".into(),
+            Self::Asc => write!(f, "asc"),
+            Self::Desc => write!(f, "desc"),
+        }
+    }
+}
+
+/// Iterate a double ended iterator from the front for `Asc`, or from the back for `Desc`.
+pub struct DirectedIter<I: DoubleEndedIterator> {
+    inner_iter: I,
+    direction: SortDirection,
+}
+
+impl<I: DoubleEndedIterator> DirectedIter<I> {
+    pub fn new(inner_iter: I, direction: SortDirection) -> Self {
+        Self {
+            inner_iter,
+            direction,
+        }
+    }
+}
+
+impl<I: DoubleEndedIterator> Iterator for DirectedIter<I> {
+    type Item = I::Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.direction {
+            SortDirection::Asc => self.inner_iter.next(),
+            SortDirection::Desc => self.inner_iter.next_back(),
         }
     }
 }
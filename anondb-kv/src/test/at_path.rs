@@ -0,0 +1,101 @@
+use super::*;
+
+fn rand_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("anondb-{}", rand::random::<u64>()))
+}
+
+#[test]
+fn mem_at_path_unsupported() -> Result<()> {
+    let path = rand_path();
+    MemKV::at_path(&path).expect_err("should fail to persist a memory kv to a path");
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[cfg(feature = "redb")]
+#[test]
+fn redb_at_path_persists() -> Result<()> {
+    let path = rand_path();
+    let table_name = rand_utf8(10);
+    let key = rand::random::<[u8; 32]>();
+    let val = rand::random::<[u8; 32]>();
+    {
+        let kv = RedbKV::at_path(&path)?;
+        kv.insert(&table_name, key.as_slice(), val.as_slice())?;
+    }
+    {
+        let kv = RedbKV::at_path(&path)?;
+        assert_eq!(kv.get(&table_name, key.as_slice())?.unwrap(), val);
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(feature = "redb")]
+#[test]
+fn redb_open_existing_missing() -> Result<()> {
+    let path = rand_path();
+    let options = RedbOptions {
+        create: false,
+        ..Default::default()
+    };
+    RedbKV::at_path_with_options(&path, options)
+        .expect_err("should fail to open a database that does not exist");
+    assert!(!path.exists());
+    Ok(())
+}
+
+#[cfg(feature = "redb")]
+#[test]
+fn redb_reject_writes() -> Result<()> {
+    let path = rand_path();
+    let table_name = rand_utf8(10);
+    let key = rand::random::<[u8; 32]>();
+    let val = rand::random::<[u8; 32]>();
+    {
+        let kv = RedbKV::at_path_with_options(
+            &path,
+            RedbOptions {
+                cache_size: Some(1024 * 1024),
+                durability: Durability::Eventual,
+                ..Default::default()
+            },
+        )?;
+        kv.insert(&table_name, key.as_slice(), val.as_slice())?;
+    }
+    {
+        let kv = RedbKV::at_path_with_options(
+            &path,
+            RedbOptions {
+                reject_writes: true,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(kv.get(&table_name, key.as_slice())?.unwrap(), val);
+        kv.insert(&table_name, key.as_slice(), val.as_slice())
+            .expect_err("should fail to write when writes are rejected");
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[cfg(feature = "fjall")]
+#[test]
+fn fjall_at_path_persists() -> Result<()> {
+    let path = rand_path();
+    let table_name = rand_utf8(10);
+    let key = rand::random::<[u8; 32]>();
+    let val = rand::random::<[u8; 32]>();
+    {
+        let kv = FjallKV::at_path(&path)?;
+        kv.insert(&table_name, key.as_slice(), val.as_slice())?;
+        kv.insert_multimap(&table_name, key.as_slice(), val.as_slice())?;
+    }
+    {
+        let kv = FjallKV::at_path(&path)?;
+        assert_eq!(kv.get(&table_name, key.as_slice())?.unwrap(), val);
+        assert_eq!(kv.count_multimap(&table_name)?, 1);
+    }
+    std::fs::remove_dir_all(&path)?;
+    Ok(())
+}
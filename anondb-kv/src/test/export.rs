@@ -0,0 +1,54 @@
+use super::*;
+
+#[domacro(all_kv_impls)]
+fn export_roundtrip<T: KV>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    let multimap_table_name = rand_utf8(10);
+    let mut entries = Vec::default();
+    for _ in 0..20 {
+        let key = rand::random::<[u8; 16]>();
+        let val = rand::random::<[u8; 32]>();
+        handle.insert(&table_name, key.as_slice(), val.as_slice())?;
+        for _ in 0..3 {
+            let multimap_val = rand::random::<[u8; 8]>();
+            handle.insert_multimap(
+                &multimap_table_name,
+                key.as_slice(),
+                multimap_val.as_slice(),
+            )?;
+        }
+        entries.push((key, val));
+    }
+
+    let bytes = handle.export_bytes()?;
+    let imported = T::in_memory(Some(&bytes))?;
+
+    assert_eq!(imported.count(&table_name)?, 20);
+    assert_eq!(imported.count_multimap(&multimap_table_name)?, 60);
+    for (key, val) in entries {
+        assert_eq!(imported.get(&table_name, key.as_slice())?.unwrap(), val);
+        let expected = handle
+            .get_multimap(&multimap_table_name, key.as_slice())?
+            .map(|item| item.map(|item| item.value().to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        let actual = imported
+            .get_multimap(&multimap_table_name, key.as_slice())?
+            .map(|item| item.map(|item| item.value().to_vec()))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(actual, expected);
+    }
+
+    // the imported kv is independent of the original
+    imported.clear(&table_name)?;
+    assert_eq!(handle.count(&table_name)?, 20);
+
+    Ok(())
+}
+
+#[domacro(all_kv_impls)]
+fn export_empty<T: KV>(handle: &T) -> Result<()> {
+    let bytes = handle.export_bytes()?;
+    let imported = T::in_memory(Some(&bytes))?;
+    assert_eq!(imported.count(&rand_utf8(10))?, 0);
+    Ok(())
+}
@@ -1,5 +1,7 @@
+mod at_path;
 mod clear;
 mod empty;
+mod export;
 mod insert;
 mod range;
 mod remove;
@@ -14,12 +16,28 @@ use crate::*;
 macro_rules! all_kv_impls {
     ($fn_name:ident) => {
         paste::paste! {
+            #[test]
+            fn [<mem_ $fn_name>]() -> Result<()> {
+                let kv = MemKV::in_memory(None)?;
+                $fn_name(&kv)?;
+                Ok(())
+            }
+
+            #[cfg(feature = "redb")]
             #[test]
             fn [<redb_ $fn_name>]() -> Result<()> {
                 let kv = RedbKV::in_memory(None)?;
                 $fn_name(&kv)?;
                 Ok(())
             }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_ $fn_name>]() -> Result<()> {
+                let kv = FjallKV::in_memory(None)?;
+                $fn_name(&kv)?;
+                Ok(())
+            }
         }
     };
 }
@@ -28,6 +46,31 @@ macro_rules! all_kv_impls {
 macro_rules! all_read_impls {
     ($fn_name:ident) => {
         paste::paste! {
+            #[test]
+            fn [<mem_ $fn_name>]() -> Result<()> {
+                let kv = MemKV::in_memory(None)?;
+                $fn_name(&kv)?;
+                Ok(())
+            }
+
+            #[test]
+            fn [<mem_read_tx_ $fn_name>]() -> Result<()> {
+                let kv = MemKV::in_memory(None)?;
+                let read = kv.read_tx()?;
+                $fn_name(&read)?;
+                Ok(())
+            }
+
+            #[test]
+            fn [<mem_write_tx_ $fn_name>]() -> Result<()> {
+                let kv = MemKV::in_memory(None)?;
+                let write = kv.write_tx()?;
+                $fn_name(&write)?;
+                write.commit()?;
+                Ok(())
+            }
+
+            #[cfg(feature = "redb")]
             #[test]
             fn [<redb_ $fn_name>]() -> Result<()> {
                 let kv = RedbKV::in_memory(None)?;
@@ -35,6 +78,7 @@ macro_rules! all_read_impls {
                 Ok(())
             }
 
+            #[cfg(feature = "redb")]
             #[test]
             fn [<redb_read_tx_ $fn_name>]() -> Result<()> {
                 let kv = RedbKV::in_memory(None)?;
@@ -43,6 +87,7 @@ macro_rules! all_read_impls {
                 Ok(())
             }
 
+            #[cfg(feature = "redb")]
             #[test]
             fn [<redb_write_tx_ $fn_name>]() -> Result<()> {
                 let kv = RedbKV::in_memory(None)?;
@@ -51,6 +96,33 @@ macro_rules! all_read_impls {
                 write.commit()?;
                 Ok(())
             }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_ $fn_name>]() -> Result<()> {
+                let kv = FjallKV::in_memory(None)?;
+                $fn_name(&kv)?;
+                Ok(())
+            }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_read_tx_ $fn_name>]() -> Result<()> {
+                let kv = FjallKV::in_memory(None)?;
+                let read = kv.read_tx()?;
+                $fn_name(&read)?;
+                Ok(())
+            }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_write_tx_ $fn_name>]() -> Result<()> {
+                let kv = FjallKV::in_memory(None)?;
+                let write = kv.write_tx()?;
+                $fn_name(&write)?;
+                write.commit()?;
+                Ok(())
+            }
         }
     };
 }
@@ -59,6 +131,23 @@ macro_rules! all_read_impls {
 macro_rules! all_read_write_impls {
     ($fn_name:ident) => {
         paste::paste! {
+            #[test]
+            fn [<mem_ $fn_name>]() -> Result<()> {
+                let kv = MemKV::in_memory(None)?;
+                $fn_name(&kv)?;
+                Ok(())
+            }
+
+            #[test]
+            fn [<mem_write_tx_ $fn_name>]() -> Result<()> {
+                let kv = MemKV::in_memory(None)?;
+                let write = kv.write_tx()?;
+                $fn_name(&write)?;
+                write.commit()?;
+                Ok(())
+            }
+
+            #[cfg(feature = "redb")]
             #[test]
             fn [<redb_ $fn_name>]() -> Result<()> {
                 let kv = RedbKV::in_memory(None)?;
@@ -66,6 +155,7 @@ macro_rules! all_read_write_impls {
                 Ok(())
             }
 
+            #[cfg(feature = "redb")]
             #[test]
             fn [<redb_write_tx_ $fn_name>]() -> Result<()> {
                 let kv = RedbKV::in_memory(None)?;
@@ -74,6 +164,24 @@ macro_rules! all_read_write_impls {
                 write.commit()?;
                 Ok(())
             }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_ $fn_name>]() -> Result<()> {
+                let kv = FjallKV::in_memory(None)?;
+                $fn_name(&kv)?;
+                Ok(())
+            }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_write_tx_ $fn_name>]() -> Result<()> {
+                let kv = FjallKV::in_memory(None)?;
+                let write = kv.write_tx()?;
+                $fn_name(&write)?;
+                write.commit()?;
+                Ok(())
+            }
         }
     };
 }
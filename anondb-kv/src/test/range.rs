@@ -1,5 +1,7 @@
 use super::*;
 
+use std::ops::Bound;
+
 #[domacro(all_kv_impls)]
 fn range_equal_len<T: KV>(handle: &T) -> Result<()> {
     const LEN: usize = 100;
@@ -115,3 +117,209 @@ fn range_var_len<T: KV>(handle: &T) -> Result<()> {
 
     Ok(())
 }
+
+#[domacro(all_kv_impls)]
+fn range_multimap<T: KV>(handle: &T) -> Result<()> {
+    const LEN: usize = 20;
+    const MIDPOINT: usize = LEN / 2;
+
+    let table_name = rand_utf8(10);
+    let mut entries = Vec::<([u8; 32], [u8; 32])>::default();
+    for _ in 0..LEN {
+        let key = rand::random::<[u8; 32]>();
+        for _ in 0..3 {
+            let val = rand::random::<[u8; 32]>();
+            handle.insert_multimap(&table_name, key.as_slice(), val.as_slice())?;
+            entries.push((key, val));
+        }
+    }
+    entries.sort();
+
+    let all_entries = handle
+        .range_multimap(&table_name, ..)?
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(all_entries.len(), entries.len());
+    for (v0, (key, val)) in all_entries.iter().zip(entries.iter()) {
+        assert_eq!(v0.key(), key);
+        assert_eq!(v0.value(), val);
+    }
+
+    let (midpoint, _) = entries.get(MIDPOINT * 3).unwrap();
+    {
+        let upper_entries = handle
+            .range_multimap(&table_name, midpoint.as_slice()..)?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(upper_entries.len(), entries[MIDPOINT * 3..].len());
+        for (v0, (key, val)) in upper_entries.iter().zip(entries[MIDPOINT * 3..].iter()) {
+            assert_eq!(v0.key(), key);
+            assert_eq!(v0.value(), val);
+        }
+    }
+    {
+        let lower_entries = handle
+            .range_multimap(&table_name, ..=midpoint.as_slice())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(lower_entries.len(), entries[..MIDPOINT * 3 + 3].len());
+    }
+    {
+        let values = handle
+            .get_multimap(&table_name, midpoint.as_slice())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(values.len(), 3);
+        for (v0, (_, val)) in values.iter().zip(entries[MIDPOINT * 3..].iter()) {
+            assert_eq!(v0.key(), midpoint);
+            assert_eq!(v0.value(), val);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare the results of a descending range with the reversed results of an ascending range.
+fn assert_range_reversed<'a, T: ReadOperations>(
+    handle: &'a T,
+    table_name: &str,
+    range: (Bound<&'a [u8]>, Bound<&'a [u8]>),
+) -> Result<()> {
+    let asc = handle
+        .range_directed(table_name, range, SortDirection::Asc)?
+        .map(|item| item.map(|item| (item.key().to_vec(), item.value().to_vec())))
+        .collect::<Result<Vec<_>>>()?;
+    let desc = handle
+        .range_directed(table_name, range, SortDirection::Desc)?
+        .map(|item| item.map(|item| (item.key().to_vec(), item.value().to_vec())))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(asc, desc.into_iter().rev().collect::<Vec<_>>());
+    Ok(())
+}
+
+#[domacro(all_read_write_impls)]
+fn range_desc<T: ReadOperations + WriteOperations>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    let mut keys = Vec::default();
+    for i in 0..20u8 {
+        let key = [i; 8];
+        handle.insert(&table_name, key.as_slice(), &[i])?;
+        keys.push(key);
+    }
+
+    // latest 3 entries
+    let latest = handle
+        .range_directed(&table_name, .., SortDirection::Desc)?
+        .take(3)
+        .map(|item| item.map(|item| item.value().to_vec()))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(latest, vec![vec![19], vec![18], vec![17]]);
+
+    let low = keys[5].as_slice();
+    let high = keys[15].as_slice();
+    for range in [
+        (Bound::Unbounded, Bound::Unbounded),
+        (Bound::Included(low), Bound::Included(high)),
+        (Bound::Included(low), Bound::Excluded(high)),
+        (Bound::Excluded(low), Bound::Included(high)),
+        (Bound::Excluded(low), Bound::Excluded(high)),
+        (Bound::Unbounded, Bound::Excluded(high)),
+        (Bound::Excluded(low), Bound::Unbounded),
+        (Bound::Included(high), Bound::Included(low)),
+    ] {
+        assert_range_reversed(handle, &table_name, range)?;
+    }
+
+    let first = handle
+        .range_directed(&table_name, ..=high, SortDirection::Desc)?
+        .next()
+        .unwrap()?;
+    assert_eq!(first.key(), high);
+    Ok(())
+}
+
+#[domacro(all_read_write_impls)]
+fn range_multimap_desc<T: ReadOperations + WriteOperations>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    for i in 0..10u8 {
+        for j in 0..3u8 {
+            handle.insert_multimap(&table_name, &[i], &[j])?;
+        }
+    }
+
+    let desc = handle
+        .range_multimap_directed(
+            &table_name,
+            [2u8].as_slice()..[5u8].as_slice(),
+            SortDirection::Desc,
+        )?
+        .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+        .collect::<Result<Vec<_>>>()?;
+    let mut expected = Vec::default();
+    for i in (2..5u8).rev() {
+        for j in (0..3u8).rev() {
+            expected.push((i, j));
+        }
+    }
+    assert_eq!(desc, expected);
+
+    let asc = handle
+        .range_multimap_directed(&table_name, .., SortDirection::Asc)?
+        .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+        .collect::<Result<Vec<_>>>()?;
+    let desc = handle
+        .range_multimap_directed(&table_name, .., SortDirection::Desc)?
+        .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(asc.len(), 30);
+    assert_eq!(asc, desc.into_iter().rev().collect::<Vec<_>>());
+    Ok(())
+}
+
+#[domacro(all_kv_impls)]
+fn range_desc_read_tx<T: KV>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    let multimap_table_name = rand_utf8(10);
+    for i in 0..10u8 {
+        handle.insert(&table_name, &[i], &[i])?;
+        handle.insert_multimap(&multimap_table_name, &[i], &[i])?;
+    }
+    let read = handle.read_tx()?;
+    let keys = read
+        .range_directed(&table_name, .., SortDirection::Desc)?
+        .map(|item| item.map(|item| item.key()[0]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(keys, (0..10u8).rev().collect::<Vec<_>>());
+    let keys = read
+        .range_multimap_directed(&multimap_table_name, .., SortDirection::Desc)?
+        .map(|item| item.map(|item| item.key()[0]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(keys, (0..10u8).rev().collect::<Vec<_>>());
+    Ok(())
+}
+
+#[domacro(all_kv_impls)]
+fn range_detached<T: KV>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    let multimap_table_name = rand_utf8(10);
+    for i in 0..10u8 {
+        handle.insert(&table_name, &[i], &[i])?;
+        handle.insert_multimap(&multimap_table_name, &[i], &[i])?;
+    }
+    let read = handle.read_tx()?;
+    let range = (Bound::Included(vec![2u8]), Bound::Unbounded);
+    let iter = read.range_detached(&table_name, range.clone(), SortDirection::Asc)?;
+    let multimap_iter =
+        read.range_multimap_detached(&multimap_table_name, range, SortDirection::Desc)?;
+    drop(read);
+
+    // writes after the read transaction was created are not visible
+    handle.insert(&table_name, &[20], &[20])?;
+    handle.insert_multimap(&multimap_table_name, &[20], &[20])?;
+
+    let keys = iter
+        .map(|item| item.map(|item| item.key()[0]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(keys, (2..10u8).collect::<Vec<_>>());
+    let keys = multimap_iter
+        .map(|item| item.map(|item| item.key()[0]))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(keys, (2..10u8).rev().collect::<Vec<_>>());
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use super::*;
 
+use std::ops::Bound;
+
 #[domacro(all_kv_impls)]
 fn write_not_visible_until_commit<T: KV>(handle: &T) -> Result<()> {
     let table_name = rand_utf8(10);
@@ -102,3 +104,181 @@ fn read_tx_consistent_over_write_multimap<T: KV>(handle: &T) -> Result<()> {
 
     Ok(())
 }
+
+#[domacro(all_kv_impls)]
+fn write_tx_reads_pending_writes<T: KV>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    let cleared_name = rand_utf8(10);
+    for i in 0..10u8 {
+        handle.insert(&table_name, &[i], &[i])?;
+        handle.insert(&cleared_name, &[i], &[i])?;
+    }
+
+    let write = handle.write_tx()?;
+    assert_eq!(write.remove(&table_name, &[2])?, Some(vec![2]));
+    assert_eq!(write.remove(&table_name, &[2])?, None);
+    assert_eq!(write.insert(&table_name, &[3], &[30])?, Some(vec![3]));
+    assert_eq!(write.insert(&table_name, &[10], &[10])?, None);
+    write.remove(&table_name, &[5])?;
+    write.insert(&table_name, &[5], &[50])?;
+    write.remove(&table_name, &[9])?;
+    write.clear(&cleared_name)?;
+    write.insert(&cleared_name, &[4], &[40])?;
+
+    let expected = [
+        (0, 0),
+        (1, 1),
+        (3, 30),
+        (4, 4),
+        (5, 50),
+        (6, 6),
+        (7, 7),
+        (8, 8),
+        (10, 10),
+    ];
+    let entries = |range: (Bound<&[u8]>, Bound<&[u8]>), direction| -> Result<Vec<(u8, u8)>> {
+        write
+            .range_directed(&table_name, range, direction)?
+            .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+            .collect()
+    };
+    let all = (Bound::Unbounded, Bound::Unbounded);
+    assert_eq!(entries(all, SortDirection::Asc)?, expected);
+    assert_eq!(
+        entries(all, SortDirection::Desc)?,
+        expected.iter().rev().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        entries(
+            (Bound::Excluded(&[1]), Bound::Included(&[5])),
+            SortDirection::Asc
+        )?,
+        vec![(3, 30), (4, 4), (5, 50)]
+    );
+    assert_eq!(write.count(&table_name)?, 9);
+    assert_eq!(write.get(&table_name, &[9])?, None);
+    assert_eq!(write.count(&cleared_name)?, 1);
+    assert_eq!(
+        write
+            .range(&cleared_name, ..)?
+            .map(|item| item.map(|item| item.key().to_vec()))
+            .collect::<Result<Vec<_>>>()?,
+        vec![vec![4]]
+    );
+    // nothing is visible outside of the transaction until commit
+    assert_eq!(handle.count(&table_name)?, 10);
+    write.commit()?;
+
+    assert_eq!(handle.count(&table_name)?, 9);
+    assert_eq!(
+        handle
+            .range(&table_name, ..)?
+            .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+            .collect::<Result<Vec<_>>>()?,
+        expected
+    );
+    assert_eq!(handle.count(&cleared_name)?, 1);
+    assert_eq!(handle.get(&cleared_name, &[4])?, Some(vec![40]));
+    Ok(())
+}
+
+#[domacro(all_kv_impls)]
+fn write_tx_reads_pending_writes_multimap<T: KV>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    for key in 0..5u8 {
+        handle.insert_multimap(&table_name, &[key], &[0])?;
+        handle.insert_multimap(&table_name, &[key], &[1])?;
+    }
+
+    let write = handle.write_tx()?;
+    assert!(write.remove_multimap(&table_name, &[1], &[0])?);
+    assert!(!write.remove_multimap(&table_name, &[1], &[0])?);
+    write.insert_multimap(&table_name, &[1], &[2])?;
+    write.remove_all_multimap(&table_name, &[3])?;
+    write.insert_multimap(&table_name, &[6], &[0])?;
+    // inserting an existing value does not change the count
+    write.insert_multimap(&table_name, &[0], &[0])?;
+
+    let expected = [
+        (0, 0),
+        (0, 1),
+        (1, 1),
+        (1, 2),
+        (2, 0),
+        (2, 1),
+        (4, 0),
+        (4, 1),
+        (6, 0),
+    ];
+    let entries = |range: (Bound<&[u8]>, Bound<&[u8]>), direction| -> Result<Vec<(u8, u8)>> {
+        write
+            .range_multimap_directed(&table_name, range, direction)?
+            .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+            .collect()
+    };
+    let all = (Bound::Unbounded, Bound::Unbounded);
+    assert_eq!(entries(all, SortDirection::Asc)?, expected);
+    assert_eq!(
+        entries(all, SortDirection::Desc)?,
+        expected.iter().rev().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        entries(
+            (Bound::Excluded(&[0]), Bound::Included(&[3])),
+            SortDirection::Desc
+        )?,
+        vec![(2, 1), (2, 0), (1, 2), (1, 1)]
+    );
+    assert_eq!(write.count_multimap(&table_name)?, 9);
+    assert_eq!(
+        write
+            .get_multimap(&table_name, &[1])?
+            .map(|item| item.map(|item| item.value().to_vec()))
+            .collect::<Result<Vec<_>>>()?,
+        vec![vec![1], vec![2]]
+    );
+    write.commit()?;
+
+    assert_eq!(handle.count_multimap(&table_name)?, 9);
+    assert_eq!(
+        handle
+            .range_multimap(&table_name, ..)?
+            .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+            .collect::<Result<Vec<_>>>()?,
+        expected
+    );
+    Ok(())
+}
+
+#[domacro(all_kv_impls)]
+fn write_tx_range_interleaved_writes<T: KV>(handle: &T) -> Result<()> {
+    let table_name = rand_utf8(10);
+    let multimap_name = rand_utf8(10);
+    let other_name = rand_utf8(10);
+    for i in 0..10u8 {
+        handle.insert(&table_name, &[i], &[i])?;
+        handle.insert_multimap(&multimap_name, &[i % 2], &[i])?;
+    }
+
+    let write = handle.write_tx()?;
+    let mut keys = Vec::default();
+    for item in write.range(&table_name, ..)? {
+        let item = item?;
+        keys.push(item.key()[0]);
+        write.remove(&table_name, item.key())?;
+        write.insert(&other_name, item.key(), item.value())?;
+    }
+    assert_eq!(keys, (0..10).collect::<Vec<_>>());
+    let mut values = Vec::default();
+    for item in write.range_multimap_directed(&multimap_name, .., SortDirection::Desc)? {
+        let item = item?;
+        values.push(item.value()[0]);
+        write.remove_multimap(&multimap_name, item.key(), item.value())?;
+    }
+    assert_eq!(values, vec![9, 7, 5, 3, 1, 8, 6, 4, 2, 0]);
+    assert_eq!(write.count(&table_name)?, 0);
+    assert_eq!(write.count_multimap(&multimap_name)?, 0);
+    assert_eq!(write.count(&other_name)?, 10);
+    write.commit()?;
+    Ok(())
+}
@@ -15,6 +15,12 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
 
     // get all the collections in the db
     let fields = parse_struct_and_fields(&input, "AnonDB")?;
+    let Some(first_field) = fields.first().and_then(|f| f.ident.clone()) else {
+        return Err(Error::new_spanned(
+            &input,
+            "AnonDB structs must contain at least one collection",
+        ));
+    };
 
     // the primary key that is defined for each field
     let mut field_primary_keys = HashMap::<Ident, IndexDef>::default();
@@ -22,22 +28,27 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
     let mut field_indices = HashMap::<Ident, Vec<IndexDef>>::default();
     // the type of the document for each field
     let mut field_doc_generic = HashMap::<Ident, Type>::default();
+    // the type of the primary key for each field, if declared
+    let mut field_primary_key_generic = HashMap::<Ident, Option<Type>>::default();
     for field in fields {
         let (primary_key, indices) = parse_attributes(field)?;
         let field_ident = field.ident.clone().expect("expected field ident to exist");
         field_primary_keys.insert(field_ident.clone(), primary_key);
         field_indices.insert(field_ident.clone(), indices);
-        let doc_generic = get_first_generic(&field.ty).unwrap();
+        let doc_generic = get_generic(&field.ty, 0).unwrap();
         field_doc_generic.insert(field_ident.clone(), doc_generic.clone());
+        let primary_key_generic = get_generic(&field.ty, 2);
+        field_primary_key_generic.insert(field_ident.clone(), primary_key_generic.cloned());
     }
 
-    for (_collection_name, indices) in &field_indices {
+    for indices in field_indices.values() {
         for index in indices {
-            for (option_name, _) in &index.options {
-                if option_name.to_string() == "primary" {
+            for option_name in index.options.keys() {
+                if *option_name == "primary" {
                     return Err(Error::new_spanned(
                         option_name,
-                        format!("Custom indices may not be primary. Use the primary_key attribute instead."),
+                        "Custom indices may not be primary. Use the primary_key attribute instead."
+                            .to_string(),
                     ));
                 }
             }
@@ -47,22 +58,37 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
     let assign_collection_vars = fields.iter().map(|f| {
         let field_name = f.ident.clone().unwrap();
         let doc_generic = field_doc_generic.get(&field_name).expect("expected field document type to be known");
+        let primary_key_generic = field_primary_key_generic.get(&field_name).expect("expected field primary key type to be known");
         let primary_key_parts = field_primary_keys.get(&field_name).unwrap();
         let primary_key_fields = primary_key_parts.fields.iter().map(|v| v.name.clone()).collect::<Vec<_>>();
         let mut all_indexed_fields = HashMap::<Ident, ()>::default();
+        for field in &primary_key_fields {
+            all_indexed_fields.insert(field.clone(), ());
+        }
         for index in field_indices.get(&field_name).cloned().unwrap_or_default() {
             for field in index.fields {
                 all_indexed_fields.insert(field.name, ());
             }
         }
 
-        let field_extractors = all_indexed_fields.iter().map(|(k, _)| {
+        let field_extractors = all_indexed_fields.keys().map(|k| {
             quote! {
                 if let Some(v) = query.#k.as_ref() {
                     out.insert(stringify!(#k).to_string(), v.into());
                 }
             }
         });
+        // assign the typed primary key serializer, a tuple of the field types for compound keys.
+        // This fails to compile if the declared primary key type does not match the primary key
+        // fields.
+        let assign_primary_key_serializer = primary_key_generic.as_ref().map(|primary_key_generic| {
+            quote! {
+                let primary_key_serializer: #crate_name::PrimaryKeySerializer<#primary_key_generic> = #crate_name::PrimaryKeyFields::serializer(&(
+                    #(<<#doc_generic as #crate_name::Queryable>::DocumentPhantom>::#primary_key_fields (),)*
+                ));
+                self.#field_name.set_primary_key_serializer(primary_key_serializer)?;
+            }
+        });
         let extract_index_fields = quote! {
             {
                 fn extractor(query: & <#doc_generic as #crate_name::Queryable> ::DocumentQuery) -> std::collections::HashMap<String, #crate_name::Param> {
@@ -138,6 +164,7 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
                     )*
                     key.take()
                 }))?;
+                #assign_primary_key_serializer
                 #extract_index_fields
                 // assign all indices
                 #(#index_assignments)*
@@ -158,9 +185,43 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
             if !self.#field_name.has_primary_key() {
                 #crate_name::anyhow::bail!("Collection \"{}\" does not have a primary key defined!", self.#field_name.name());
             }
+            self.#field_name.describe(&mut metadata);
+        }
+    });
+
+    let apply_schema_changes = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! {
+            self.#field_name.apply_schema_changes(tx, changes, pending)?;
+        }
+    });
+
+    let init_index_builds = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! {
+            self.#field_name.init_index_builds()?;
+        }
+    });
+
+    let build_chunks = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! {
+            is_complete &= self.#field_name.build_indices_chunk(chunk_size)?;
+        }
+    });
+
+    let build_progress = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! {
+            out.extend(self.#field_name.index_build_progress()?);
         }
     });
 
+    let collection_names = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! { stringify!(#field_name) }
+    });
+
     let defaults = fields.iter().map(|f| {
         let field_name = &f.ident;
         quote! {
@@ -174,10 +235,84 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
     // the concrete KV implementation for the database
     let kv_generic_name = get_kv_generic(&input)?;
 
+    // a struct with a transactional handle for each collection
+    let tx_name = quote::format_ident!("{}Tx", name);
+    let tx_fields = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        let doc_generic = field_doc_generic
+            .get(f.ident.as_ref().unwrap())
+            .expect("expected field document type to be known");
+        let primary_key_generic = field_primary_key_generic
+            .get(f.ident.as_ref().unwrap())
+            .expect("expected field primary key type to be known")
+            .as_ref()
+            .map(|ty| quote! { , #ty });
+        quote! {
+            pub #field_name: #crate_name::CollectionTx<'tx, #doc_generic, #kv_generic_name #primary_key_generic>,
+        }
+    });
+    let tx_assignments = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! {
+            #field_name: #crate_name::CollectionTx::new(&self.#field_name, &tx),
+        }
+    });
+    // a struct with a snapshot handle for each collection
+    let snapshot_name = quote::format_ident!("{}Snapshot", name);
+    let snapshot_fields = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        let doc_generic = field_doc_generic
+            .get(f.ident.as_ref().unwrap())
+            .expect("expected field document type to be known");
+        let primary_key_generic = field_primary_key_generic
+            .get(f.ident.as_ref().unwrap())
+            .expect("expected field primary key type to be known")
+            .as_ref()
+            .map(|ty| quote! { , #ty });
+        quote! {
+            pub #field_name: #crate_name::CollectionSnapshot<'s, #doc_generic, #kv_generic_name #primary_key_generic>,
+        }
+    });
+    let snapshot_assignments = fields.iter().map(|f| {
+        let field_name = &f.ident;
+        quote! {
+            #field_name: #crate_name::CollectionSnapshot::new(&self.#field_name, tx.clone()),
+        }
+    });
+    let mut snapshot_generics = input.generics.clone();
+    let snapshot_lifetime = Lifetime::new("'s", proc_macro2::Span::call_site());
+    snapshot_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeParam::new(snapshot_lifetime)),
+    );
+    let (snapshot_impl_generics, _, snapshot_where_clause) = snapshot_generics.split_for_impl();
+
+    let mut tx_generics = input.generics.clone();
+    tx_generics.params.insert(
+        0,
+        GenericParam::Lifetime(LifetimeParam::new(Lifetime::new(
+            "'tx",
+            proc_macro2::Span::call_site(),
+        ))),
+    );
+    let (tx_impl_generics, _, tx_where_clause) = tx_generics.split_for_impl();
+
     // types on the struct
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
     let expanded = quote! {
+        /// Handles to every collection of the database that share a single write transaction.
+        /// Created by the `transaction` function.
+        pub struct #tx_name #tx_impl_generics #tx_where_clause {
+            #(#tx_fields)*
+        }
+
+        /// Handles to every collection of the database that read from a single snapshot.
+        /// Created by the `snapshot` function.
+        pub struct #snapshot_name #snapshot_impl_generics #snapshot_where_clause {
+            #(#snapshot_fields)*
+        }
+
         impl #impl_generics Default for #name #ty_generics #where_clause {
             fn default() -> Self {
                 Self {
@@ -191,7 +326,7 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
             pub fn in_memory(bytes_maybe: Option<&[u8]>) -> #crate_name::anyhow::Result<::std::sync::Arc<Self>> {
                 let mut s = Self::default();
                 let kv = ::std::sync::Arc::new(#kv_generic_name::in_memory(bytes_maybe)?);
-                s.setup(kv)?;
+                s.setup(kv, &#crate_name::Migrations::default())?;
                 Ok(::std::sync::Arc::new(s))
             }
 
@@ -199,20 +334,102 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
             pub fn at_path(path: &::std::path::Path) -> #crate_name::anyhow::Result<::std::sync::Arc<Self>> {
                 let mut s = Self::default();
                 let kv = ::std::sync::Arc::new(#kv_generic_name::at_path(path)?);
-                s.setup(kv)?;
+                s.setup(kv, &#crate_name::Migrations::default())?;
+                Ok(::std::sync::Arc::new(s))
+            }
+
+            /// Initialize the database backed by an already constructed kv. Use this to open a
+            /// kv with implementation specific options.
+            pub fn from_kv(kv: #kv_generic_name) -> #crate_name::anyhow::Result<::std::sync::Arc<Self>> {
+                Self::with_migrations(kv, &#crate_name::Migrations::default())
+            }
+
+            /// Initialize the database backed by an already constructed kv, migrating stored
+            /// documents written at an older schema version. See `Migrations`.
+            pub fn with_migrations(kv: #kv_generic_name, migrations: &#crate_name::Migrations) -> #crate_name::anyhow::Result<::std::sync::Arc<Self>> {
+                let mut s = Self::default();
+                s.setup(::std::sync::Arc::new(kv), migrations)?;
                 Ok(::std::sync::Arc::new(s))
             }
 
+            /// Get a reference to the kv backing the database. All collections share this kv.
+            pub fn kv(&self) -> &::std::sync::Arc<#kv_generic_name> {
+                self.#first_field.kv()
+            }
+
+            /// Serialize the full contents of the database into bytes accepted by `in_memory`.
+            pub fn export_bytes(&self) -> #crate_name::anyhow::Result<Vec<u8>> {
+                self.kv().export_bytes()
+            }
+
+            /// Build every new index in chunks of `chunk_size` documents until all are complete.
+            /// Each chunk is a separate write transaction, so the database remains usable while
+            /// this runs, for example on a background thread. An interrupted build resumes on the
+            /// next call. Fails if an index cannot be built, for example because documents
+            /// violate a unique constraint, see `Collection::build_indices_chunk`.
+            pub fn build_indices(&self, chunk_size: usize) -> #crate_name::anyhow::Result<()> {
+                loop {
+                    let mut is_complete = true;
+                    #(#build_chunks)*
+                    if is_complete {
+                        return Ok(());
+                    }
+                }
+            }
+
+            /// Report the progress of every index that is being built.
+            pub fn index_build_progress(&self) -> #crate_name::anyhow::Result<Vec<#crate_name::IndexBuildProgress>> {
+                let mut out = Vec::default();
+                #(#build_progress)*
+                Ok(out)
+            }
+
+            /// Open a point in time view of every collection. Queries through the snapshot do not
+            /// see writes made after it was opened.
+            pub fn snapshot(&self) -> #crate_name::anyhow::Result<#snapshot_name<'_, #kv_generic_name>> {
+                let tx = ::std::sync::Arc::new(#crate_name::anondb_kv::KV::read_tx(self.kv().as_ref())?);
+                Ok(#snapshot_name {
+                    #(#snapshot_assignments)*
+                })
+            }
+
+            /// Run `f` against a snapshot of every collection. See `snapshot`.
+            pub fn read<R>(&self, f: impl FnOnce(&#snapshot_name<'_, #kv_generic_name>) -> #crate_name::anyhow::Result<R>) -> #crate_name::anyhow::Result<R> {
+                f(&self.snapshot()?)
+            }
+
+            /// Run `f` with every collection sharing a single write transaction. All writes are
+            /// committed if `f` returns `Ok`, and discarded if it returns `Err`.
+            pub fn transaction<R>(&self, f: impl FnOnce(&#tx_name<'_, #kv_generic_name>) -> #crate_name::anyhow::Result<R>) -> #crate_name::anyhow::Result<R> {
+                let tx = #crate_name::anondb_kv::KV::write_tx(self.kv().as_ref())?;
+                let out = f(&#tx_name {
+                    #(#tx_assignments)*
+                })?;
+                #crate_name::anondb_kv::WriteTx::commit(tx)?;
+                Ok(out)
+            }
+
             /// Assign collection variables based on struct values.
-            fn setup(&mut self, kv: ::std::sync::Arc<#kv_generic_name>) -> #crate_name::anyhow::Result<()> {
+            fn setup(&mut self, kv: ::std::sync::Arc<#kv_generic_name>, migrations: &#crate_name::Migrations) -> #crate_name::anyhow::Result<()> {
                 // assign values to the collection such as kv, name, indices
                 #(#assign_collection_vars)*
 
                 let mut all_table_names = ::std::collections::HashMap::<String, String>::default();
+                // tables reserved for database internals
+                all_table_names.insert(#crate_name::INDEX_BUILD_TABLE.into(), "anondb".into());
+                all_table_names.insert(#crate_name::METADATA_TABLE.into(), "anondb".into());
+                let mut metadata = #crate_name::MetadataDocument::default();
                 // Check the consistency of the database configuration. Check for conflicting
-                // collection/index names. In the future read a configuration from the kv to
-                // automatically detect schema changes and check for inconsistencies.
+                // collection/index names, and describe each collection.
                 #(#collection_checks)*
+                migrations.check(&[#(#collection_names),*])?;
+                // compare with the schema stored in the kv, refusing incompatible changes,
+                // migrating documents and applying changes to indices
+                metadata.sync(kv.as_ref(), migrations, |tx, changes, pending| {
+                    #(#apply_schema_changes)*
+                    Ok(())
+                })?;
+                #(#init_index_builds)*
                 Ok(())
             }
         }
@@ -235,7 +452,7 @@ fn parse_attributes(field: &Field) -> Result<(IndexDef, Vec<IndexDef>)> {
                 if !index_def.options.is_empty() {
                     return Err(Error::new_spanned(
                         attr,
-                        format!("AnonDB primary_key attribute does not support options"),
+                        "AnonDB primary_key attribute does not support options".to_string(),
                     ));
                 }
                 primary_key_maybe = Some(index_def);
@@ -259,7 +476,7 @@ fn parse_attributes(field: &Field) -> Result<(IndexDef, Vec<IndexDef>)> {
             field,
             format!(
                 "AnonDB collection \"{}\" does not have a primary key specified. You may do so with #[anondb(primary_key = field_name)]",
-                field.ident.clone().unwrap().to_string()
+                field.ident.clone().unwrap()
             ),
         ));
     }
@@ -307,19 +524,17 @@ fn get_kv_generic(input: &DeriveInput) -> Result<&Ident> {
     Ok(kv_generic_name)
 }
 
-fn get_first_generic(ty: &Type) -> Option<&Type> {
+/// Get the generic type argument at `index`, e.g. the document type of a collection at index 0.
+fn get_generic(ty: &Type, index: usize) -> Option<&Type> {
     // Check if the type is a path (e.g., Vec<T>, Option<String>)
     if let Type::Path(type_path) = ty {
         // Get the last segment (e.g., "Vec" in "std::vec::Vec")
         if let Some(segment) = type_path.path.segments.last() {
             // Check if it has generic arguments
             if let PathArguments::AngleBracketed(args) = &segment.arguments {
-                // Get the first generic argument
-                if let Some(first_arg) = args.args.first() {
-                    // Extract the type from the generic argument
-                    if let GenericArgument::Type(ty) = first_arg {
-                        return Some(ty);
-                    }
+                // Get the generic argument at index if it is a type
+                if let Some(GenericArgument::Type(ty)) = args.args.iter().nth(index) {
+                    return Some(ty);
                 }
             }
         }
@@ -1,6 +1,7 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::Result;
+use syn::ext::IdentExt;
 use syn::*;
 
 use super::*;
@@ -52,6 +53,42 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
             }
         }
     });
+    let container_serde = parse_serde_attributes(&input.attrs, SerdeTarget::Container)?;
+    let mut field_schemas = Vec::default();
+    for f in fields {
+        let field_name = f.ident.clone().unwrap();
+        let field_type = &f.ty;
+        let serde = parse_serde_attributes(&f.attrs, SerdeTarget::Field)?;
+        // skipped fields are never stored
+        if serde.skip {
+            continue;
+        }
+        let optional = is_option(field_type);
+        let default = serde.default || container_serde.default;
+        if serde.skip_serializing_if && !optional && !default {
+            return Err(Error::new_spanned(
+                f,
+                "Document fields with `skip_serializing_if` must be an Option or have a default, otherwise stored documents may not deserialize",
+            ));
+        }
+        let name = field_name.unraw().to_string();
+        let serialized_name = match (serde.rename, &container_serde.rename_all) {
+            (Some(rename), _) => rename,
+            (None, Some(rule)) => rule.apply(&name),
+            (None, None) => name.clone(),
+        };
+        // stringify without the spacing added by the tokenizer, e.g. `Vec<u8>`
+        let ty = quote!(#field_type).to_string().replace(' ', "");
+        field_schemas.push(quote! {
+            #crate_name::FieldSchema {
+                name: #name,
+                serialized_name: #serialized_name,
+                ty: #ty,
+                optional: #optional,
+                default: #default,
+            }
+        });
+    }
     let query_struct_name = quote::format_ident!("{}_Query", name);
     let phantom_struct_name = quote::format_ident!("{}_Phantom", name);
 
@@ -78,6 +115,7 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
         impl #impl_generics #crate_name::Queryable for #name #ty_generics #where_clause {
             type DocumentQuery = #query_struct_name;
             type DocumentPhantom = #phantom_struct_name;
+            const FIELDS: &'static [#crate_name::FieldSchema] = &[#(#field_schemas),*];
 
             fn matches(&self, query: &Self::DocumentQuery) -> bool {
                 #(#match_entries)*
@@ -88,3 +126,168 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream> {
 
     Ok(TokenStream::from(expanded))
 }
+
+/// The serde attributes that affect how a document is stored.
+#[derive(Default)]
+struct SerdeAttributes {
+    rename: Option<String>,
+    rename_all: Option<RenameRule>,
+    default: bool,
+    skip: bool,
+    skip_serializing_if: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SerdeTarget {
+    Container,
+    Field,
+}
+
+/// A serde `rename_all` rule, applied to field names.
+#[derive(Clone, Copy, PartialEq)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(name: &LitStr) -> Result<Self> {
+        Ok(match name.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            _ => return Err(Error::new_spanned(name, "unknown serde rename rule")),
+        })
+    }
+
+    /// Rename a snake case field name, matching serde.
+    fn apply(&self, field: &str) -> String {
+        match self {
+            Self::Lower | Self::Snake => field.to_string(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal | Self::Camel => {
+                let mut out = String::default();
+                let mut capitalize = *self == Self::Pascal;
+                for c in field.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        out.push(c.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        out.push(c);
+                    }
+                }
+                out
+            }
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+/// Extract the `#[serde(...)]` attributes that change how a document is stored. Attributes that
+/// only affect reading, like `alias`, are ignored. Attributes that change the stored shape in a
+/// way the schema cannot describe are rejected.
+fn parse_serde_attributes(attrs: &[Attribute], target: SerdeTarget) -> Result<SerdeAttributes> {
+    const UNSUPPORTED_CONTAINER: &[&str] = &[
+        "transparent",
+        "from",
+        "try_from",
+        "into",
+        "remote",
+        "tag",
+        "content",
+        "untagged",
+    ];
+    const UNSUPPORTED_FIELD: &[&str] = &[
+        "flatten",
+        "with",
+        "serialize_with",
+        "deserialize_with",
+        "skip_serializing",
+        "skip_deserializing",
+        "getter",
+    ];
+    let unsupported = match target {
+        SerdeTarget::Container => UNSUPPORTED_CONTAINER,
+        SerdeTarget::Field => UNSUPPORTED_FIELD,
+    };
+    let mut out = SerdeAttributes::default();
+    for attr in attrs {
+        if !attr.path().is_ident("serde") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if unsupported.iter().any(|name| meta.path.is_ident(name)) {
+                return Err(meta.error(format!(
+                    "serde attribute `{}` is not supported by Document, the stored schema cannot describe it",
+                    meta.path.get_ident().unwrap()
+                )));
+            }
+            if meta.path.is_ident("rename") && meta.input.peek(Token![=]) {
+                let name: LitStr = meta.value()?.parse()?;
+                out.rename = Some(name.value());
+            } else if meta.path.is_ident("rename_all") && meta.input.peek(Token![=]) {
+                let name: LitStr = meta.value()?.parse()?;
+                out.rename_all = Some(RenameRule::parse(&name)?);
+            } else if meta.path.is_ident("rename") || meta.path.is_ident("rename_all") {
+                // rename(serialize = "..", deserialize = "..")
+                let is_all = meta.path.is_ident("rename_all");
+                meta.parse_nested_meta(|meta| {
+                    let name: LitStr = meta.value()?.parse()?;
+                    if !meta.path.is_ident("serialize") {
+                        return Ok(());
+                    }
+                    if is_all {
+                        out.rename_all = Some(RenameRule::parse(&name)?);
+                    } else {
+                        out.rename = Some(name.value());
+                    }
+                    Ok(())
+                })?;
+            } else if meta.path.is_ident("default") {
+                out.default = true;
+                if meta.input.peek(Token![=]) {
+                    let _: LitStr = meta.value()?.parse()?;
+                }
+            } else if meta.path.is_ident("skip") {
+                out.skip = true;
+            } else if meta.path.is_ident("skip_serializing_if") {
+                out.skip_serializing_if = true;
+                let _: LitStr = meta.value()?.parse()?;
+            } else if meta.input.peek(Token![=]) {
+                let _: Expr = meta.value()?.parse()?;
+            } else if meta.input.peek(token::Paren) {
+                let content;
+                parenthesized!(content in meta.input);
+                content.parse::<proc_macro2::TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+/// Returns `true` if the type is an `Option`.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
@@ -11,6 +11,8 @@ use anondb_kv::SortDirection;
 #[derive(Clone)]
 pub struct IndexField {
     pub name: Ident,
+    // parsed but not yet used when generating indices
+    #[allow(dead_code)]
     pub direction: SortDirection,
 }
 
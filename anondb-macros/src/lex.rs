@@ -0,0 +1,287 @@
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::Result;
+use syn::*;
+
+use super::*;
+
+/// Implement `SerializeLexicographic` for a struct or enum. Structs are encoded field by field.
+/// Enums are encoded as the discriminant of the variant, followed by the fields of the variant.
+/// Without explicit discriminants this is the index of the variant in the narrowest unsigned
+/// integer that fits, otherwise it is the declared discriminant in the `repr` type of the enum.
+/// Keys sort in the same order as `#[derive(PartialOrd)]`.
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let crate_name = crate_name();
+    let lex = quote! { #crate_name::anondb_kv::SerializeLexicographic };
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#lex));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (serialize, min, max, fixed_width) = match &input.data {
+        Data::Struct(data) => {
+            let types = data.fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+            let members = data.fields.members();
+            let serialize = quote! {
+                let mut out = Vec::<u8>::default();
+                #(out.extend(#lex::serialize_lex(&self.#members));)*
+                out
+            };
+            let (min, max, fixed_width) = bounds(&lex, &types);
+            (serialize, min, max, fixed_width)
+        }
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(Error::new_spanned(
+                    &input,
+                    "SerializeLexicographic cannot be derived for enums without variants",
+                ));
+            }
+            let explicit = data.variants.iter().any(|v| v.discriminant.is_some());
+            let (discriminant_ty, discriminants) = if explicit {
+                (repr_ty(&input)?, declared_discriminants(data))
+            } else {
+                // the narrowest integer that can hold every variant index
+                let discriminant_ty = if data.variants.len() <= u8::MAX as usize + 1 {
+                    quote! { u8 }
+                } else if data.variants.len() <= u16::MAX as usize + 1 {
+                    quote! { u16 }
+                } else {
+                    quote! { u32 }
+                };
+                let discriminants = (0..data.variants.len() as u32)
+                    .map(|i| quote! { #i })
+                    .collect::<Vec<_>>();
+                (discriminant_ty, discriminants)
+            };
+            let prefixes = discriminants
+                .iter()
+                .map(|d| quote! { #lex::serialize_lex(&((#d) as #discriminant_ty)) })
+                .collect::<Vec<_>>();
+            let arms = data
+                .variants
+                .iter()
+                .zip(&prefixes)
+                .map(|(variant, prefix)| {
+                    let variant_name = &variant.ident;
+                    let bindings = (0..variant.fields.len())
+                        .map(|i| quote::format_ident!("field_{i}"))
+                        .collect::<Vec<_>>();
+                    let members = variant.fields.members();
+                    let pattern = match &variant.fields {
+                        Fields::Named(_) => quote! { { #(#members: #bindings),* } },
+                        Fields::Unnamed(_) => quote! { ( #(#bindings),* ) },
+                        Fields::Unit => quote! {},
+                    };
+                    quote! {
+                        Self::#variant_name #pattern => {
+                            out.extend(#prefix);
+                            #(out.extend(#lex::serialize_lex(#bindings));)*
+                        }
+                    }
+                });
+            let serialize = quote! {
+                let mut out = Vec::<u8>::default();
+                match self {
+                    #(#arms)*
+                }
+                out
+            };
+
+            // min and max are the variants with the smallest and largest discriminant, with their
+            // smallest and largest fields. Discriminants may be constant expressions, so the
+            // variant is selected when the function is called.
+            let variant_count = data.variants.len();
+            let mins = data
+                .variants
+                .iter()
+                .zip(&prefixes)
+                .map(|(variant, prefix)| {
+                    let types = variant.fields.iter().map(|f| &f.ty);
+                    quote! {{
+                        let mut out = #prefix;
+                        #(out.extend(<#types as #lex>::min());)*
+                        out
+                    }}
+                });
+            let maxes = data
+                .variants
+                .iter()
+                .zip(&prefixes)
+                .map(|(variant, prefix)| {
+                    let types = variant.fields.iter().map(|f| &f.ty);
+                    quote! {
+                        (#prefix, || {
+                            let mut out = Vec::<u8>::default();
+                            #(out.extend(<#types as #lex>::max()?);)*
+                            Some(out)
+                        })
+                    }
+                });
+            let min = quote! {
+                [#(#mins),*].into_iter().min().unwrap()
+            };
+            let max = quote! {
+                let variants: [(Vec<u8>, fn() -> Option<Vec<u8>>); #variant_count] = [#(#maxes),*];
+                let (mut out, fields) = variants
+                    .into_iter()
+                    .max_by(|a, b| a.0.cmp(&b.0))
+                    .unwrap();
+                out.extend(fields()?);
+                Some(out)
+            };
+
+            // the width is only fixed if every variant has the same fixed width
+            let widths = data.variants.iter().map(|variant| {
+                let types = variant.fields.iter().map(|f| &f.ty);
+                quote! { Some(0 #(+ <#types as #lex>::fixed_width()?)*) }
+            });
+            let fixed_width = quote! {
+                let widths = [#((|| #widths)()),*];
+                let width = widths[0]?;
+                if widths.iter().any(|w| *w != Some(width)) {
+                    return None;
+                }
+                Some(<#discriminant_ty as #lex>::fixed_width()? + width)
+            };
+            (serialize, min, max, fixed_width)
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input,
+                "SerializeLexicographic can only be derived for structs and enums, not unions",
+            ));
+        }
+    };
+
+    let expanded = quote! {
+        // fieldless types never extend their output
+        #[allow(unused_mut)]
+        impl #impl_generics #lex for #name #ty_generics #where_clause {
+            fn serialize_lex(&self) -> Vec<u8> {
+                #serialize
+            }
+
+            fn min() -> Vec<u8> {
+                #min
+            }
+
+            fn max() -> Option<Vec<u8>> {
+                #max
+            }
+
+            fn fixed_width() -> Option<u32> {
+                #fixed_width
+            }
+        }
+
+        impl #impl_generics From<#name #ty_generics> for #crate_name::ParamTyped<#name #ty_generics> #where_clause {
+            fn from(value: #name #ty_generics) -> Self {
+                #crate_name::ParamTyped::Eq(value)
+            }
+        }
+
+        impl #impl_generics From<::std::ops::Range<#name #ty_generics>> for #crate_name::ParamTyped<#name #ty_generics> #where_clause {
+            fn from(value: ::std::ops::Range<#name #ty_generics>) -> Self {
+                #crate_name::ParamTyped::Range(#crate_name::GeneralRange(
+                    ::std::ops::Bound::Included(value.start),
+                    ::std::ops::Bound::Excluded(value.end),
+                ))
+            }
+        }
+
+        impl #impl_generics From<::std::ops::RangeInclusive<#name #ty_generics>> for #crate_name::ParamTyped<#name #ty_generics> #where_clause {
+            fn from(value: ::std::ops::RangeInclusive<#name #ty_generics>) -> Self {
+                let (start, end) = value.into_inner();
+                #crate_name::ParamTyped::Range(#crate_name::GeneralRange(
+                    ::std::ops::Bound::Included(start),
+                    ::std::ops::Bound::Included(end),
+                ))
+            }
+        }
+    };
+
+    Ok(TokenStream::from(expanded))
+}
+
+/// Build the bodies of `min`, `max` and `fixed_width` for a sequence of fields. The width is fixed
+/// only if every field has a fixed width.
+fn bounds(
+    lex: &proc_macro2::TokenStream,
+    types: &[&Type],
+) -> (
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
+) {
+    let min = quote! {
+        let mut out = Vec::<u8>::default();
+        #(out.extend(<#types as #lex>::min());)*
+        out
+    };
+    let max = quote! {
+        let mut out = Vec::<u8>::default();
+        #(out.extend(<#types as #lex>::max()?);)*
+        Some(out)
+    };
+    let fixed_width = quote! {
+        Some(0 #(+ <#types as #lex>::fixed_width()?)*)
+    };
+    (min, max, fixed_width)
+}
+
+/// The integer type of the discriminants of an enum, from its `repr` attribute. Defaults to
+/// `isize`, like the compiler.
+fn repr_ty(input: &DeriveInput) -> Result<proc_macro2::TokenStream> {
+    const INTEGERS: &[&str] = &[
+        "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+    ];
+    let mut repr = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("repr") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                if INTEGERS.contains(&ident.to_string().as_str()) {
+                    repr = Some(ident.clone());
+                }
+            }
+            // skip the arguments of other representations, e.g. `align(8)`
+            if meta.input.peek(token::Paren) {
+                let _args;
+                parenthesized!(_args in meta.input);
+            }
+            Ok(())
+        })?;
+    }
+    Ok(match repr {
+        Some(ident) => quote! { #ident },
+        None => quote! { isize },
+    })
+}
+
+/// The discriminant of every variant. Variants without an explicit discriminant are one more
+/// than the previous variant, starting from 0.
+fn declared_discriminants(data: &DataEnum) -> Vec<proc_macro2::TokenStream> {
+    let mut base = None;
+    let mut offset = 0u32;
+    let mut out = Vec::with_capacity(data.variants.len());
+    for variant in &data.variants {
+        if let Some((_, expr)) = &variant.discriminant {
+            base = Some(expr);
+            offset = 0;
+        }
+        let offset_lit = proc_macro2::Literal::u32_unsuffixed(offset);
+        out.push(match base {
+            Some(expr) if offset == 0 => quote! { #expr },
+            Some(expr) => quote! { (#expr) + #offset_lit },
+            None => quote! { #offset_lit },
+        });
+        offset += 1;
+    }
+    out
+}
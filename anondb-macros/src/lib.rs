@@ -1,6 +1,7 @@
 mod anondb;
 mod document;
 mod index;
+mod lex;
 
 use index::*;
 
@@ -27,6 +28,15 @@ pub fn document_derive(input: TokenStream) -> TokenStream {
     }
 }
 
+#[proc_macro_derive(SerializeLexicographic)]
+pub fn serialize_lexicographic_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match lex::derive(input) {
+        Ok(t) => t,
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 fn crate_name() -> proc_macro2::TokenStream {
     if std::env::var("CARGO_PKG_NAME").ok().as_deref() == Some("anondb") {
         quote::quote! { crate }
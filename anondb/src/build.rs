@@ -0,0 +1,283 @@
+use std::ops::Bound;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::*;
+use anondb_kv::*;
+
+/// Reserved table tracking the build state of every secondary index, keyed by index table name.
+pub const INDEX_BUILD_TABLE: &str = "_anondb_index_builds";
+
+/// Persisted build state of a secondary index. Indices added to the schema of a collection that
+/// has documents must be built before they can be used.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexBuildState {
+    pub complete: bool,
+    /// Primary key of the last document added to the index. The build resumes after this key.
+    pub cursor: Option<Vec<u8>>,
+    /// Number of documents added to the index so far.
+    pub processed: u64,
+    /// Error that stopped the build, e.g. a unique constraint violation. A failed build is not
+    /// retried, `Collection::rebuild_indices` builds the index again once the documents are fixed.
+    #[serde(default)]
+    pub failed: Option<String>,
+}
+
+impl IndexBuildState {
+    pub fn complete() -> Self {
+        Self {
+            complete: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn load(tx: &impl ReadOperations, table_name: &str) -> Result<Option<Self>> {
+        match tx.get(INDEX_BUILD_TABLE, table_name.as_bytes())? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn store(&self, tx: &impl WriteTx, table_name: &str) -> Result<()> {
+        tx.insert(
+            INDEX_BUILD_TABLE,
+            table_name.as_bytes(),
+            &rmp_serde::to_vec_named(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Progress of an index that is being built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexBuildProgress {
+    pub collection: String,
+    /// Table name of the index.
+    pub index: String,
+    /// Number of documents added to the index.
+    pub processed: u64,
+    /// Number of documents in the collection.
+    pub total: u64,
+    /// Error that stopped the build, if it failed.
+    pub failed: Option<String>,
+}
+
+impl<T, K: KV, PK> Collection<T, K, PK>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    /// Load the build state of every index. Indices added to the schema are registered when the
+    /// schema is synced, see `register_index_build`. An index without a state predates build
+    /// tracking, and is complete if the collection is empty or the index table has entries.
+    /// Otherwise it must be built with `build_indices_chunk` before the planner will use it. This
+    /// should be automatically invoked by the AnonDB proc macro.
+    pub fn init_index_builds(&self) -> Result<()> {
+        let mut building = self.building.write().unwrap();
+        let mut untracked = Vec::default();
+        {
+            let tx = self.kv().read_tx()?;
+            for index in self.indices() {
+                let table_name = index.table_name();
+                match IndexBuildState::load(&tx, &table_name)? {
+                    Some(state) if state.complete => {}
+                    Some(state) => {
+                        if let Some(error) = state.failed {
+                            log::warn!(
+                                "Collection \"{}\" index \"{}\" build failed: {error}",
+                                self.name(),
+                                table_name
+                            );
+                        }
+                        building.insert(table_name);
+                    }
+                    None => untracked.push(index),
+                }
+            }
+        }
+        // only open a write transaction when needed so databases rejecting writes can be opened
+        if untracked.is_empty() {
+            return Ok(());
+        }
+        let tx = self.kv().write_tx()?;
+        let is_empty = tx.count(self.name())? == 0;
+        for index in untracked {
+            let table_name = index.table_name();
+            let entries = if index.options.unique {
+                tx.count(&table_name)?
+            } else {
+                tx.count_multimap(&table_name)?
+            };
+            if is_empty || entries > 0 {
+                IndexBuildState::complete().store(&tx, &table_name)?;
+            } else {
+                IndexBuildState::default().store(&tx, &table_name)?;
+                building.insert(table_name);
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Register an index added to the schema. Over an empty collection the index is complete
+    /// immediately, otherwise its table is cleared and the index must be built with
+    /// `build_indices_chunk`.
+    pub(crate) fn register_index_build(
+        &self,
+        tx: &K::WriteTransaction,
+        index: &Index<T>,
+    ) -> Result<()> {
+        let table_name = index.table_name();
+        if tx.count(self.name())? == 0 {
+            return IndexBuildState::complete().store(tx, &table_name);
+        }
+        // discard anything left by an index that previously used this table
+        if index.options.unique {
+            tx.clear(&table_name)?;
+        } else {
+            tx.clear_multimap(&table_name)?;
+        }
+        log::info!(
+            "Collection \"{}\" index \"{}\" added, it is not used by queries until built with build_indices",
+            self.name(),
+            table_name
+        );
+        IndexBuildState::default().store(tx, &table_name)
+    }
+
+    /// Returns `true` if the index is usable by the query planner.
+    pub fn is_index_complete(&self, index: &Index<T>) -> bool {
+        !self.building.read().unwrap().contains(&index.table_name())
+    }
+
+    /// Report the progress of every index that is being built.
+    pub fn index_build_progress(&self) -> Result<Vec<IndexBuildProgress>> {
+        let tx = self.kv().read_tx()?;
+        let total = tx.count(self.name())?;
+        let mut out = Vec::default();
+        for index in self.indices() {
+            if self.is_index_complete(index) {
+                continue;
+            }
+            let table_name = index.table_name();
+            let state = IndexBuildState::load(&tx, &table_name)?.unwrap_or_default();
+            out.push(IndexBuildProgress {
+                collection: self.name().to_string(),
+                index: table_name,
+                processed: state.processed,
+                total,
+                failed: state.failed,
+            });
+        }
+        Ok(out)
+    }
+
+    /// Add up to `chunk_size` documents to the first incomplete index in a single write
+    /// transaction. The position of the build is persisted in the same transaction, so an
+    /// interrupted build resumes where it stopped. Returns `true` once every index is complete.
+    ///
+    /// Writes to the collection keep every index up to date while it is being built, so the
+    /// database remains usable between chunks. If a document cannot be added, for example because
+    /// a document written during the build violates a unique constraint, the build is marked as
+    /// failed and the error is returned. Failed builds are skipped by later calls, which fail once
+    /// no other index remains to be built.
+    pub fn build_indices_chunk(&self, chunk_size: usize) -> Result<bool> {
+        if chunk_size == 0 {
+            anyhow::bail!(
+                "Collection \"{}\" index build chunk size must be greater than 0",
+                self.name()
+            );
+        }
+        if self.building.read().unwrap().is_empty() {
+            return Ok(true);
+        }
+        let tx = self.kv().write_tx()?;
+        // the build state and the documents are read through the write transaction that advances
+        // the cursor, so a conflicting writer cannot slip between the read and the commit
+        let mut next = None;
+        let mut failed = None;
+        for index in self.indices() {
+            if self.is_index_complete(index) {
+                continue;
+            }
+            let state = IndexBuildState::load(&tx, &index.table_name())?.unwrap_or_default();
+            match state.failed {
+                Some(error) => {
+                    failed.get_or_insert((index.table_name(), error));
+                }
+                None => {
+                    next = Some((index, state));
+                    break;
+                }
+            }
+        }
+        let Some((index, mut state)) = next else {
+            if let Some((table_name, error)) = failed {
+                anyhow::bail!(
+                    "Collection \"{}\" index \"{}\" build failed: {error}",
+                    self.name(),
+                    table_name
+                );
+            }
+            return Ok(true);
+        };
+        let table_name = index.table_name();
+        let complete = match self.build_chunk(&tx, index, &mut state, chunk_size)? {
+            Ok(complete) => complete,
+            Err(e) => {
+                // discard the chunk, and persist the failure so the build is not retried
+                drop(tx);
+                let tx = self.kv().write_tx()?;
+                let mut state = IndexBuildState::load(&tx, &table_name)?.unwrap_or_default();
+                state.failed = Some(e.to_string());
+                state.store(&tx, &table_name)?;
+                tx.commit()?;
+                return Err(e);
+            }
+        };
+        if complete {
+            state = IndexBuildState::complete();
+        }
+        state.store(&tx, &table_name)?;
+        tx.commit()?;
+        if state.complete {
+            self.building.write().unwrap().remove(&table_name);
+        }
+        Ok(self.building.read().unwrap().is_empty())
+    }
+
+    /// Add up to `chunk_size` documents after the cursor of `state` to an index. The inner result
+    /// is whether the index is complete, or the error that stopped the build at a document.
+    fn build_chunk(
+        &self,
+        tx: &K::WriteTransaction,
+        index: &Index<T>,
+        state: &mut IndexBuildState,
+        chunk_size: usize,
+    ) -> Result<Result<bool>> {
+        let cursor = state.cursor.take();
+        let start = match cursor.as_deref() {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+        let mut remaining = chunk_size;
+        // the range is read lazily, so a chunk only reads the documents it adds
+        for item in tx.range(self.name(), (start, Bound::Unbounded))? {
+            let item = item?;
+            if remaining == 0 {
+                return Ok(Ok(false));
+            }
+            let added = rmp_serde::from_slice::<T>(item.value())
+                .map_err(anyhow::Error::from)
+                .and_then(|document| index.build_entry(tx, &document, item.key()));
+            if let Err(e) = added {
+                return Ok(Err(e));
+            }
+            state.processed += 1;
+            state.cursor = Some(item.key().to_vec());
+            remaining -= 1;
+        }
+        Ok(Ok(true))
+    }
+}
@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::sync::RwLock;
 
 use anyhow::Result;
 use serde::Deserialize;
@@ -9,8 +11,43 @@ use serde::Serialize;
 use super::*;
 use anondb_kv::*;
 
+/// Extracts the index compatible fields from a query, keyed by field name.
+pub type FieldExtractor<Q> = fn(&Q) -> HashMap<String, Param>;
+
+/// How `Collection::insert_many` handles documents that violate a unique constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InsertMode {
+    /// Insert nothing if any document cannot be inserted.
+    #[default]
+    Atomic,
+    /// Skip documents that violate a unique constraint (including the primary key) and insert the
+    /// rest. Skipped documents are reported in the `InsertReport`.
+    SkipDuplicates,
+}
+
+/// A document that was not inserted by `Collection::insert_many`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedDocument {
+    /// Position of the document in the input.
+    pub position: usize,
+    /// Table name of the unique index that was violated.
+    pub index: String,
+}
+
+/// The outcome of `Collection::insert_many`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InsertReport {
+    pub inserted: u64,
+    pub skipped: Vec<SkippedDocument>,
+}
+
+/// A collection of documents of type `T` stored in a kv of type `K`. `PK` is the type of the
+/// primary key: the type of the primary key field, or a tuple of the field types in primary key
+/// order for a compound primary key. The AnonDB derive macro checks it against the primary key
+/// attribute. If `PK` is not declared the collection works as normal, except that documents cannot
+/// be accessed by primary key through `get`, `get_many` and `delete`.
 #[derive(Debug)]
-pub struct Collection<T, K: KV>
+pub struct Collection<T, K: KV, PK = UndeclaredKey>
 where
     T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
 {
@@ -21,11 +58,17 @@ where
     indices: Vec<Arc<Index<T>>>,
     /// Extractor function to get a primary key from an instance of T
     primary_key_index: Option<Arc<Index<T>>>,
+    /// Serializer from a typed primary key to the bytes stored in the primary index
+    primary_key_serializer: Option<PrimaryKeySerializer<PK>>,
     /// Take a query and extract all fields that are index compatible
-    extract_index_fields: Option<fn(&T::DocumentQuery) -> HashMap<String, Param>>,
+    extract_index_fields: Option<FieldExtractor<T::DocumentQuery>>,
+    /// Table names of indices that are being built. These are not used by the query planner. This
+    /// is local to the process: another process writing to the same store will not see it, and
+    /// only learns of completed builds when it reloads the build state.
+    pub(crate) building: RwLock<HashSet<String>>,
 }
 
-impl<T, K: KV> Collection<T, K>
+impl<T, K: KV, PK> Collection<T, K, PK>
 where
     T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
 {
@@ -36,8 +79,10 @@ where
             kv: None,
             name: None,
             primary_key_index: None,
+            primary_key_serializer: None,
             extract_index_fields: None,
             indices: Vec::default(),
+            building: RwLock::default(),
         }
     }
 
@@ -49,7 +94,7 @@ where
     /// A function to set the primary key without consuming `self`. Used in the AnonDB proc macro.
     pub fn set_primary_key(
         &mut self,
-        primary_key: (Vec<(String, LexStats)>, fn(&T) -> Vec<u8>),
+        primary_key: (Vec<(String, LexStats)>, KeySerializer<T>),
     ) -> Result<()> {
         if self.primary_key_index.is_some() {
             anyhow::bail!(
@@ -69,24 +114,50 @@ where
         Ok(())
     }
 
+    /// Set the serializer used to look up documents by a typed primary key. Used in the AnonDB
+    /// proc macro.
+    pub fn set_primary_key_serializer(
+        &mut self,
+        serializer: PrimaryKeySerializer<PK>,
+    ) -> Result<()> {
+        if self.primary_key_serializer.is_some() {
+            anyhow::bail!(
+                "Collection \"{}\" attempting to assign primary key serializer twice!",
+                self.name()
+            );
+        }
+        self.primary_key_serializer = Some(serializer);
+        Ok(())
+    }
+
+    /// Serialize a typed primary key to the key used in the collection table.
+    pub(crate) fn serialize_primary_key(&self, primary_key: &PK) -> Vec<u8> {
+        let serializer = self.primary_key_serializer.unwrap_or_else(|| {
+            panic!(
+                "Collection \"{}\" has no primary key serializer set!",
+                self.name()
+            )
+        });
+        serializer(primary_key)
+    }
+
     pub fn primary_key_index(&self) -> &Arc<Index<T>> {
         self.primary_key_index
             .as_ref()
             .expect("No primary key index set!")
     }
 
-    pub fn set_field_extractor(
-        &mut self,
-        extractor: fn(&T::DocumentQuery) -> HashMap<String, Param>,
-    ) {
+    pub fn set_field_extractor(&mut self, extractor: FieldExtractor<T::DocumentQuery>) {
         self.extract_index_fields = Some(extractor);
     }
 
     pub fn extract_index_fields(&self, query: &T::DocumentQuery) -> HashMap<String, Param> {
-        (self.extract_index_fields.expect(&format!(
-            "In collection \"{}\", no index field extractor set!",
-            self.name()
-        )))(query)
+        (self.extract_index_fields.unwrap_or_else(|| {
+            panic!(
+                "In collection \"{}\", no index field extractor set!",
+                self.name()
+            )
+        }))(query)
     }
 
     /// Set the name of the collection. This should be automatically invoked by the AnonDB proc
@@ -116,10 +187,10 @@ where
     }
 
     /// Get a reference to the backing KV.
-    pub(crate) fn kv(&self) -> &Arc<K> {
+    pub fn kv(&self) -> &Arc<K> {
         self.kv
             .as_ref()
-            .expect(&format!("Collection \"{}\" has no kv set!", self.name()))
+            .unwrap_or_else(|| panic!("Collection \"{}\" has no kv set!", self.name()))
     }
 
     /// Get a reference to indices associated with this collection, keyed to their kv table name.
@@ -128,14 +199,11 @@ where
     }
 
     /// Get a reference to the primary key extractor.
-    fn primary_key_extractor(&self) -> &fn(&T) -> Vec<u8> {
+    pub(crate) fn primary_key_extractor(&self) -> &KeySerializer<T> {
         self.primary_key_index
             .as_ref()
             .map(|index| &index.serialize)
-            .expect(&format!(
-                "Collection \"{}\" has no primary key set!",
-                self.name()
-            ))
+            .unwrap_or_else(|| panic!("Collection \"{}\" has no primary key set!", self.name()))
     }
 
     /// Define an index with a `name`, and a set of fields and their sort direction. This will
@@ -193,7 +261,7 @@ where
 
     /// Return all the table names that this collection uses in the underlying KV.
     pub fn table_names(&self) -> Vec<String> {
-        vec![
+        [
             vec![self.name().to_string()],
             self.indices()
                 .iter()
@@ -216,102 +284,166 @@ where
         self.kv().count(self.name())
     }
 
+    /// Run `f` against this collection in a single write transaction. The transaction is committed
+    /// if `f` returns `Ok`, otherwise all writes are discarded.
+    fn write<R>(&self, f: impl FnOnce(&CollectionTx<'_, T, K, PK>) -> Result<R>) -> Result<R> {
+        let tx = self.kv().write_tx()?;
+        let out = f(&CollectionTx::new(self, &tx))?;
+        tx.commit()?;
+        Ok(out)
+    }
+
     /// Insert a document into a collection. All relevant indices will be updated.
     pub fn insert(&self, document: &T) -> Result<()> {
-        let tx = self.kv().write_tx()?;
-        let primary_key = (self.primary_key_extractor())(document);
+        self.write(|tx| tx.insert(document))
+    }
 
-        self.primary_key_index()
-            .insert(&tx, document, &primary_key)?;
-        for index in &self.indices {
-            index.insert(&tx, document, &primary_key)?;
-        }
-        tx.commit()?;
-        Ok(())
+    /// Insert many documents in a single write transaction. All relevant indices will be updated.
+    /// Documents are checked for unique constraints against stored documents and documents earlier
+    /// in the input. See `InsertMode` for how violations are handled.
+    pub fn insert_many<'a>(
+        &self,
+        documents: impl IntoIterator<Item = &'a T>,
+        mode: InsertMode,
+    ) -> Result<InsertReport> {
+        self.write(|tx| tx.insert_many(documents, mode))
+    }
+
+    /// Replace the stored document with the same primary key as `document`. All relevant indices
+    /// will be updated and unique constraints are checked against the new document. Fails if no
+    /// document exists with the primary key.
+    pub fn replace(&self, document: &T) -> Result<()> {
+        self.write(|tx| tx.replace(document))
+    }
+
+    /// Insert a document, or overwrite the stored document with the same primary key. Only index
+    /// entries that changed are rewritten, and unique constraints are checked against other
+    /// documents. Returns the overwritten document, if one existed.
+    pub fn upsert(&self, document: &T) -> Result<Option<T>> {
+        self.write(|tx| tx.upsert(document))
+    }
+
+    /// Apply `update` to every document matching a query in a single write transaction. Returns the
+    /// number of documents updated. All matching documents are loaded into memory.
+    ///
+    /// Fails without writing anything if `update` changes the primary key fields of a document.
+    pub fn update(&self, query: T::DocumentQuery, update: impl FnMut(&mut T)) -> Result<u64> {
+        self.write(|tx| tx.update(query, update))
+    }
+
+    /// Delete the document with a primary key. Returns the removed document, if one existed. All
+    /// index entries for the document are removed.
+    pub fn delete(&self, primary_key: &PK) -> Result<Option<T>> {
+        self.write(|tx| tx.delete(primary_key))
+    }
+
+    /// Delete the first document matching a query. Returns the removed document, if any.
+    pub fn delete_one(&self, query: T::DocumentQuery) -> Result<Option<T>> {
+        self.write(|tx| tx.delete_one(query))
+    }
+
+    /// Delete every document matching a query in a single write transaction. Returns the number of
+    /// documents removed.
+    pub fn delete_many(&self, query: T::DocumentQuery) -> Result<u64> {
+        self.write(|tx| tx.delete_many(query))
     }
 
     /// Clear all indices for this collection and completely rebuild them. This operation is `O(N)`
     /// over the number of documents in the collection.
+    ///
+    /// The rebuild happens in a single write transaction. If it fails, for example because a
+    /// unique constraint is violated, or is interrupted, the existing indices are left unchanged.
     pub fn rebuild_indices(&self) -> Result<()> {
-        // first empty all index collections
-        let tx = self.kv().write_tx()?;
-        for index in &self.indices {
-            tx.clear(&index.table_name())?;
-        }
-        tx.commit()?;
-
-        // then iterate over all documents and construct indices
-        self.kv().scan(self.name(), |primary_key, val| {
-            let data = rmp_serde::from_slice(val)?;
+        self.write(|tx| {
             for index in &self.indices {
-                let key = (index.serialize)(&data);
-                // we insert the document primary key as the value and the indexed bytes as the
-                // key to get lexicographic iteration
-                self.kv()
-                    .insert(&index.table_name(), key.as_slice(), primary_key)?;
+                tx.rebuild_index(index)?;
             }
-            return Ok(true);
+            Ok(())
         })?;
+        self.building.write().unwrap().clear();
         Ok(())
     }
 
-    pub fn find_many(&self, query: T::DocumentQuery) -> Result<impl Iterator<Item = T>> {
-        let index_fields = self.extract_index_fields(&query);
-
+    /// Select the index that will most efficiently answer a query.
+    pub(crate) fn best_index(
+        &self,
+        query: &T::DocumentQuery,
+        index_fields: &HashMap<String, Param>,
+    ) -> Result<Arc<Index<T>>> {
         let mut scores = BTreeMap::default();
-        let primary_index_score = self
-            .primary_key_index()
-            .query_compat(&query, &index_fields)?;
+        let primary_index_score = self.primary_key_index().query_compat(query, index_fields)?;
         scores.insert(primary_index_score, self.primary_key_index().clone());
 
         for index in self.indices() {
-            let score = index.query_compat(&query, &index_fields)?;
+            if !self.is_index_complete(index) {
+                continue;
+            }
+            let score = index.query_compat(query, index_fields)?;
             scores.insert(score, index.clone());
         }
-        let (score, best_index) = scores
+        scores
             .last_key_value()
-            .map(|(k, v)| (*k, v.clone()))
-            .ok_or(anyhow::anyhow!("no index found"))?;
-        println!(
-            "using index \"{}\" score: {}",
-            best_index.table_name(),
-            score
-        );
-        let tx = self.kv().read_tx()?;
-        Ok(best_index
-            .query(&tx, &query, &index_fields)?
-            .collect::<Vec<_>>()
-            .into_iter())
+            .map(|(_, index)| index.clone())
+            .ok_or(anyhow::anyhow!("no index found"))
     }
 
-    pub fn find_one(&self, query: T::DocumentQuery) -> Result<Option<T>> {
+    /// Lazily iterate over all documents matching a query. Documents are read from a single
+    /// snapshot of the kv, and are only loaded as the iterator advances.
+    pub fn find_many(
+        &self,
+        query: T::DocumentQuery,
+    ) -> Result<DocumentIter<T, K::ReadTransaction>> {
+        self.find_many_in(self.kv().read_tx()?, query)
+    }
+
+    /// Lazily iterate over all documents matching a query using an existing read transaction.
+    pub(crate) fn find_many_in<Tx: ReadTx>(
+        &self,
+        tx: Tx,
+        query: T::DocumentQuery,
+    ) -> Result<DocumentIter<T, Tx>> {
         let index_fields = self.extract_index_fields(&query);
+        let best_index = self.best_index(&query, &index_fields)?;
+        best_index.query(tx, query, &index_fields)
+    }
 
-        let mut scores = BTreeMap::default();
-        let primary_index_score = self
-            .primary_key_index()
-            .query_compat(&query, &index_fields)?;
-        scores.insert(primary_index_score, self.primary_key_index().clone());
-        for index in self.indices() {
-            let score = index.query_compat(&query, &index_fields)?;
-            scores.insert(score, index.clone());
-        }
-        if let Some((score, best_index)) = scores.last_key_value() {
-            println!(
-                "using index \"{}\" score: {}",
-                best_index.table_name(),
-                score
-            );
-            let tx = self.kv().read_tx()?;
-            let mut out = best_index.query(&tx, &query, &index_fields)?;
-            Ok(out.next())
-        } else {
-            unreachable!("no index found!");
+    pub fn find_one(&self, query: T::DocumentQuery) -> Result<Option<T>> {
+        self.find_many(query)?.next().transpose()
+    }
+
+    /// Retrieve a document by primary key.
+    pub fn get(&self, primary_key: &PK) -> Result<Option<T>> {
+        let key = self.serialize_primary_key(primary_key);
+        match self.kv().get(self.name(), key.as_slice())? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
         }
     }
+
+    /// Retrieve many documents by primary key from a single snapshot of the kv. The output is in
+    /// the same order as the input keys, with `None` for keys that do not exist.
+    pub fn get_many<'a>(
+        &self,
+        primary_keys: impl IntoIterator<Item = &'a PK>,
+    ) -> Result<Vec<Option<T>>>
+    where
+        PK: 'a,
+    {
+        let tx = self.kv().read_tx()?;
+        primary_keys
+            .into_iter()
+            .map(|primary_key| {
+                let key = self.serialize_primary_key(primary_key);
+                match tx.get(self.name(), key.as_slice())? {
+                    Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+                    None => Ok(None),
+                }
+            })
+            .collect()
+    }
 }
 
-impl<T, K: KV> Default for Collection<T, K>
+impl<T, K: KV, PK> Default for Collection<T, K, PK>
 where
     T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
 {
@@ -322,8 +454,10 @@ where
             kv: None,
             name: None,
             primary_key_index: None,
+            primary_key_serializer: None,
             extract_index_fields: None,
             indices: Vec::default(),
+            building: RwLock::default(),
         }
     }
 }
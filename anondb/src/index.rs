@@ -18,6 +18,9 @@ pub struct IndexOptions {
                       // name
 }
 
+/// Serializes a document into a lexicographically sortable key.
+pub type KeySerializer<T> = fn(&T) -> Vec<u8>;
+
 // TODO: explicitly check and disallow duplicate field names
 #[derive(Debug, Clone, PartialEq)]
 pub struct Index<T>
@@ -29,7 +32,7 @@ where
     /// The field names of the document type along with the byte length (if constant)
     pub field_names: Vec<(String, LexStats)>,
     /// Take a document of type `T` and serialize it into a lexicographically sortable key
-    pub serialize: fn(&T) -> Vec<u8>,
+    pub serialize: KeySerializer<T>,
     /// Options for the index
     pub options: IndexOptions,
 }
@@ -58,55 +61,53 @@ where
     }
 
     /// TODO: allow variable length serialization only as the final element in an index
-    /// Accept a set of field keys as lexicographically serialized bytes
-    pub fn query<'tx>(
-        &self,
-        tx: &'tx impl ReadOperations,
-        query: &T::DocumentQuery,
-        index_fields: &HashMap<String, Param>,
-    ) -> Result<impl Iterator<Item = T>> {
-        let mut min_key = LexicographicKey::default();
-        let mut max_key = LexicographicKey::default();
-        let mut min_bound: Bound<Vec<u8>> = Bound::Unbounded;
-        let mut max_bound: Bound<Vec<u8>> = Bound::Unbounded;
+    /// Accept a set of field keys as lexicographically serialized bytes and determine the ranges
+    /// of the index table that contain all matching documents. Each value of an `In` parameter
+    /// produces a separate range. `Neq` and `Nin` produce the ranges between excluded values.
+    pub fn scan_ranges(&self, index_fields: &HashMap<String, Param>) -> Vec<GeneralRange<Vec<u8>>> {
+        let mut ranges = vec![KeyRange::default()];
         for (name, lex_stats) in &self.field_names {
             if let Some(query_param) = index_fields.get(name) {
                 match query_param {
                     Param::Eq(v) => {
-                        min_key.append_key_slice(v);
-                        max_key.append_key_slice(v);
-                        min_bound = Bound::Included(min_key.to_vec());
-                        max_bound = Bound::Included({
-                            let mut v = max_key.clone();
-                            v.append_upper_inclusive_byte();
-                            v.take()
-                        });
+                        for range in &mut ranges {
+                            range.append_eq(v);
+                        }
+                    }
+                    Param::In(v) => {
+                        let mut values = v.clone();
+                        values.sort();
+                        values.dedup();
+                        let mut next_ranges = Vec::with_capacity(ranges.len() * values.len());
+                        for range in &ranges {
+                            for value in &values {
+                                let mut range = range.clone();
+                                range.append_eq(value);
+                                next_ranges.push(range);
+                            }
+                        }
+                        ranges = next_ranges;
                     }
-                    Param::In(_v) => {
-                        unimplemented!()
+                    Param::Neq(v) => {
+                        ranges = ranges
+                            .into_iter()
+                            .flat_map(|range| range.append_excluding(std::slice::from_ref(v)))
+                            .collect();
+                        break;
                     }
-                    Param::Nin(_) | Param::Neq(_) => {
+                    Param::Nin(v) => {
+                        let mut values = v.clone();
+                        values.sort();
+                        values.dedup();
+                        ranges = ranges
+                            .into_iter()
+                            .flat_map(|range| range.append_excluding(&values))
+                            .collect();
                         break;
                     }
                     Param::Range(v) => {
-                        match v.start_bound() {
-                            Bound::Unbounded => {}
-                            Bound::Included(v) | Bound::Excluded(v) => {
-                                // we always treat it as included to account for earlier fields
-                                // that may exist in the key
-                                min_key.append_key_slice(&v);
-                                min_bound = Bound::Included(min_key.take());
-                            }
-                        }
-                        match v.end_bound() {
-                            Bound::Unbounded => {
-                                max_bound = Bound::Unbounded;
-                            }
-                            Bound::Included(v) | Bound::Excluded(v) => {
-                                max_key.append_key_slice(&v);
-                                max_key.append_upper_inclusive_byte();
-                                max_bound = Bound::Included(max_key.take());
-                            }
+                        for range in &mut ranges {
+                            range.append_range(v);
                         }
                         break;
                     }
@@ -115,16 +116,9 @@ where
                 // the query isn't using this field of the index. If this field is constant width
                 // we can continue attempting to use the index.
                 if let Some(width) = lex_stats.fixed_width {
-                    let min = vec![0u8; width as usize];
-                    let max = vec![u8::MAX; width as usize];
-                    min_key.append_key_slice(&min);
-                    max_key.append_key_slice(&max);
-                    min_bound = Bound::Included(min_key.to_vec());
-                    max_bound = Bound::Included({
-                        let mut v = max_key.clone();
-                        v.append_upper_inclusive_byte();
-                        v.take()
-                    });
+                    for range in &mut ranges {
+                        range.append_fixed_width(width);
+                    }
                     continue;
                 }
 
@@ -132,48 +126,98 @@ where
                 break;
             }
         }
-        let scan_range = GeneralRange(min_bound, max_bound);
-        println!("{:?}", scan_range);
+        ranges
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| range.into())
+            .collect()
+    }
+
+    /// Lazily iterate over the documents in this index that match a query. The returned iterator
+    /// takes ownership of the read transaction. If the query is split over multiple ranges the
+    /// results are merged in key order.
+    pub fn query<Tx: ReadTx>(
+        &self,
+        tx: Tx,
+        query: T::DocumentQuery,
+        index_fields: &HashMap<String, Param>,
+    ) -> Result<DocumentIter<T, Tx>> {
+        let scan_ranges = self.scan_ranges(index_fields);
         let table_name = self.table_name();
-        let docs = if self.options.unique {
-            tx.range_buffered(&table_name, scan_range.as_slice(), |_k, v, _done| {
-                // v represents the primary key, we'll load the document and check it against the
-                // selector
-                let doc_bytes = if self.options.primary {
-                    v.to_vec()
-                } else {
-                    tx.get(&self.collection_name, v)?.ok_or_else(|| {
-                        anyhow::anyhow!(
-                            "Index \"{table_name}\" referencing primary key that does not exist!"
-                        )
-                    })?
-                };
-                // parse the bytes
-                let doc = rmp_serde::from_slice::<T>(&doc_bytes)?;
-                if doc.matches(query) {
-                    Ok(Some(doc))
-                } else {
-                    Ok(None)
-                }
-            })?
+        let mut iters = Vec::with_capacity(scan_ranges.len());
+        for scan_range in scan_ranges {
+            let range = (scan_range.0, scan_range.1);
+            iters.push(if self.options.unique {
+                tx.range_detached(&table_name, range, SortDirection::Asc)?
+            } else {
+                // multimap index never stores full documents, the iterator loads them from the
+                // primary table
+                tx.range_multimap_detached(&table_name, range, SortDirection::Asc)?
+            });
+        }
+        Ok(DocumentIter::new(tx, merge_ranges(iters), query, self))
+    }
+
+    /// Load the documents in this index that match a query, stopping after `limit` documents.
+    /// Works with any transaction, including a write transaction, in which case its uncommitted
+    /// writes are visible. Documents are loaded eagerly so the transaction may be written to while
+    /// the results are in use.
+    pub fn query_buffered(
+        &self,
+        tx: &impl ReadOperations,
+        query: &T::DocumentQuery,
+        index_fields: &HashMap<String, Param>,
+        limit: Option<usize>,
+    ) -> Result<Vec<T>> {
+        let scan_ranges = self.scan_ranges(index_fields);
+        let ranges = scan_ranges.iter().map(|scan_range| {
+            (
+                scan_range.0.as_ref().map(Vec::as_slice),
+                scan_range.1.as_ref().map(Vec::as_slice),
+            )
+        });
+        let table_name = self.table_name();
+        if self.options.unique {
+            let iters = ranges
+                .map(|range| Ok(Box::new(tx.range(&table_name, range)?) as RangeIter<_>))
+                .collect::<Result<Vec<_>>>()?;
+            self.load_matching(tx, merge_ranges(iters), query, limit)
         } else {
-            tx.range_buffered_multimap(&table_name, scan_range.as_slice(), |_k, v, _done| {
-                // multimap index never stores full documents, always load from the primary table
-                let doc_bytes = tx.get(&self.collection_name, v)?.ok_or_else(|| {
-                    anyhow::anyhow!(
-                        "Index \"{table_name}\" referencing primary key that does not exist!"
-                    )
-                })?;
-                // parse the bytes
-                let doc = rmp_serde::from_slice::<T>(&doc_bytes)?;
-                if doc.matches(query) {
-                    Ok(Some(doc))
-                } else {
-                    Ok(None)
+            let iters = ranges
+                .map(|range| Ok(Box::new(tx.range_multimap(&table_name, range)?) as RangeIter<_>))
+                .collect::<Result<Vec<_>>>()?;
+            self.load_matching(tx, merge_ranges(iters), query, limit)
+        }
+    }
+
+    /// Load the documents referenced by index entries that match a query, stopping after `limit`
+    /// documents.
+    fn load_matching(
+        &self,
+        tx: &impl ReadOperations,
+        entries: RangeIter<'_, impl OpaqueItem>,
+        query: &T::DocumentQuery,
+        limit: Option<usize>,
+    ) -> Result<Vec<T>> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let table_name = self.table_name();
+        let mut out = Vec::default();
+        for item in entries {
+            let doc = load_document::<T>(
+                tx,
+                &self.collection_name,
+                &table_name,
+                self.options.primary,
+                item?.value(),
+            )?;
+            if doc.matches(query) {
+                out.push(doc);
+                if out.len() >= limit {
+                    break;
                 }
-            })?
-        };
-        Ok(docs.into_iter())
+            }
+        }
+        Ok(out)
     }
 
     /// Determine how compatible this index is with a given query. A higher score indicates a
@@ -261,4 +305,190 @@ where
         }
         Ok(())
     }
+
+    /// Add the entry for a document while building the index. Unlike `insert` this accepts an
+    /// entry that already exists for the same document.
+    pub fn build_entry(&self, tx: &impl WriteTx, doc: &T, primary_key: &[u8]) -> Result<()> {
+        let key = (self.serialize)(doc);
+        let table_name = self.table_name();
+        if !self.options.unique {
+            tx.insert_multimap(&table_name, key.as_slice(), primary_key)?;
+            return Ok(());
+        }
+        match tx.get(&table_name, key.as_slice())? {
+            Some(existing) if existing == primary_key => {}
+            Some(_) => anyhow::bail!(
+                "Collection \"{}\" index \"{}\" cannot build index, uniqueness constraint violated",
+                self.collection_name,
+                table_name
+            ),
+            None => {
+                tx.insert(&table_name, key.as_slice(), primary_key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if inserting `doc` would violate a unique constraint of this index.
+    pub fn is_violated_by(&self, tx: &impl ReadOperations, doc: &T) -> Result<bool> {
+        if !self.options.unique {
+            return Ok(false);
+        }
+        let key = (self.serialize)(doc);
+        Ok(tx.get(&self.table_name(), key.as_slice())?.is_some())
+    }
+
+    /// Replace the index entry for `old_doc` with the entry for `doc`. Both documents must have the
+    /// same primary key. The index is only written if the key changed, in which case unique
+    /// constraints are checked against other documents.
+    pub fn replace(
+        &self,
+        tx: &impl WriteTx,
+        old_doc: &T,
+        doc: &T,
+        primary_key: &[u8],
+    ) -> Result<()> {
+        if self.options.primary {
+            tx.insert(
+                &self.table_name(),
+                primary_key,
+                &rmp_serde::to_vec_named(doc)?,
+            )?;
+            return Ok(());
+        }
+        if (self.serialize)(old_doc) == (self.serialize)(doc) {
+            return Ok(());
+        }
+        self.remove(tx, old_doc, primary_key)?;
+        self.insert(tx, doc, primary_key)
+    }
+
+    /// Take a document and a primary key and remove the matching entry from the index.
+    pub fn remove(&self, tx: &impl WriteTx, doc: &T, primary_key: &[u8]) -> Result<()> {
+        let key = (self.serialize)(doc);
+        let table_name = self.table_name();
+        if self.options.unique {
+            tx.remove(&table_name, key.as_slice())?;
+        } else {
+            tx.remove_multimap(&table_name, key.as_slice(), primary_key)?;
+        }
+        Ok(())
+    }
+}
+
+/// A range of index keys under construction. Fields are appended in index order.
+#[derive(Clone)]
+struct KeyRange {
+    min_key: LexicographicKey,
+    max_key: LexicographicKey,
+    min_bound: Bound<Vec<u8>>,
+    max_bound: Bound<Vec<u8>>,
+}
+
+impl Default for KeyRange {
+    fn default() -> Self {
+        Self {
+            min_key: LexicographicKey::default(),
+            max_key: LexicographicKey::default(),
+            min_bound: Bound::Unbounded,
+            max_bound: Bound::Unbounded,
+        }
+    }
+}
+
+impl KeyRange {
+    /// Restrict the next field to a single value.
+    fn append_eq(&mut self, v: &[u8]) {
+        self.min_key.append_key_slice(v);
+        self.max_key.append_key_slice(v);
+        self.min_bound = Bound::Included(self.min_key.to_vec());
+        self.max_bound = Bound::Included({
+            let mut v = self.max_key.clone();
+            v.append_upper_inclusive_byte();
+            v.take()
+        });
+    }
+
+    /// Restrict the next field to any value of a fixed width.
+    fn append_fixed_width(&mut self, width: u32) {
+        let min = vec![0u8; width as usize];
+        let max = vec![u8::MAX; width as usize];
+        self.min_key.append_key_slice(&min);
+        self.max_key.append_key_slice(&max);
+        self.min_bound = Bound::Included(self.min_key.to_vec());
+        self.max_bound = Bound::Included({
+            let mut v = self.max_key.clone();
+            v.append_upper_inclusive_byte();
+            v.take()
+        });
+    }
+
+    /// Restrict the next field to a range. No further fields may be appended.
+    fn append_range(&mut self, v: &GeneralRange<Vec<u8>>) {
+        match v.start_bound() {
+            Bound::Unbounded => {}
+            Bound::Included(v) | Bound::Excluded(v) => {
+                // we always treat it as included to account for earlier fields
+                // that may exist in the key
+                self.min_key.append_key_slice(v);
+                self.min_bound = Bound::Included(self.min_key.to_vec());
+            }
+        }
+        match v.end_bound() {
+            // keep the bound of the earlier fields
+            Bound::Unbounded => {}
+            Bound::Included(v) | Bound::Excluded(v) => {
+                self.max_key.append_key_slice(v);
+                self.max_key.append_upper_inclusive_byte();
+                self.max_bound = Bound::Included(self.max_key.to_vec());
+            }
+        }
+    }
+
+    /// Restrict the next field to any value except those in `values`, which must be sorted.
+    /// Produces a range below, between, and above the excluded values. No further fields may be
+    /// appended.
+    fn append_excluding(self, values: &[Vec<u8>]) -> Vec<KeyRange> {
+        if self.min_key.as_slice() != self.max_key.as_slice() {
+            // earlier fields span multiple values, so the excluded keys are not contiguous.
+            // Scan the whole range and rely on the document filter instead.
+            return vec![self];
+        }
+        let mut out = Vec::with_capacity(values.len() + 1);
+        let mut lower = self.min_bound.clone();
+        for v in values {
+            let mut key = self.min_key.clone();
+            key.append_key_slice(v);
+            out.push(KeyRange {
+                min_bound: lower,
+                max_bound: Bound::Excluded(key.to_vec()),
+                ..self.clone()
+            });
+            // skip all keys with this value in the field
+            key.append_upper_inclusive_byte();
+            lower = Bound::Included(key.take());
+        }
+        out.push(KeyRange {
+            min_bound: lower,
+            ..self
+        });
+        out
+    }
+
+    /// Determine if the range cannot contain any keys.
+    fn is_empty(&self) -> bool {
+        match (&self.min_bound, &self.max_bound) {
+            (Bound::Included(min), Bound::Included(max)) => min > max,
+            (Bound::Included(min), Bound::Excluded(max))
+            | (Bound::Excluded(min), Bound::Included(max))
+            | (Bound::Excluded(min), Bound::Excluded(max)) => min >= max,
+            _ => false,
+        }
+    }
+}
+
+impl From<KeyRange> for GeneralRange<Vec<u8>> {
+    fn from(value: KeyRange) -> Self {
+        GeneralRange(value.min_bound, value.max_bound)
+    }
 }
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use anondb_kv::*;
+
+use crate::*;
+
+/// Lazily loads the documents referenced by a range of an index and yields those matching a
+/// query. The iterator owns its read transaction, so all documents are read from the same
+/// snapshot and nothing is buffered between calls to `next`.
+pub struct DocumentIter<T, Tx>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Queryable,
+    Tx: ReadTx,
+{
+    // declared before `tx` so the range is dropped first
+    inner_iter: DetachedIter<Tx::Item>,
+    tx: Tx,
+    query: T::DocumentQuery,
+    collection_name: String,
+    table_name: String,
+    /// Index entries hold the full document instead of a primary key.
+    is_primary: bool,
+}
+
+impl<T, Tx> DocumentIter<T, Tx>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Queryable,
+    Tx: ReadTx,
+{
+    pub fn new(
+        tx: Tx,
+        inner_iter: DetachedIter<Tx::Item>,
+        query: T::DocumentQuery,
+        index: &Index<T>,
+    ) -> Self {
+        Self {
+            inner_iter,
+            tx,
+            query,
+            collection_name: index.collection_name.clone(),
+            table_name: index.table_name(),
+            is_primary: index.options.primary,
+        }
+    }
+
+    fn next_doc(&mut self) -> Result<Option<T>> {
+        for item in self.inner_iter.by_ref() {
+            let doc = load_document::<T>(
+                &self.tx,
+                &self.collection_name,
+                &self.table_name,
+                self.is_primary,
+                item?.value(),
+            )?;
+            if doc.matches(&self.query) {
+                return Ok(Some(doc));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<T, Tx> Iterator for DocumentIter<T, Tx>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Queryable,
+    Tx: ReadTx,
+{
+    type Item = Result<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_doc().transpose()
+    }
+}
+
+/// Load the document referenced by the value of an index entry. The value is either the document
+/// or a primary key referencing it.
+pub fn load_document<T: for<'de> Deserialize<'de>>(
+    tx: &impl ReadOperations,
+    collection_name: &str,
+    table_name: &str,
+    is_primary: bool,
+    value: &[u8],
+) -> Result<T> {
+    if is_primary {
+        return Ok(rmp_serde::from_slice::<T>(value)?);
+    }
+    let doc_bytes = tx.get(collection_name, value)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Index \"{}\" referencing primary key that does not exist!",
+            table_name
+        )
+    })?;
+    Ok(rmp_serde::from_slice::<T>(&doc_bytes)?)
+}
+
+/// An iterator over the items of a table, which may borrow the transaction it reads from.
+pub type RangeIter<'a, I> = Box<dyn Iterator<Item = Result<I>> + 'a>;
+
+/// Combine iterators over ranges of a table, merging them in key order if there is more than one.
+pub fn merge_ranges<'a, I: OpaqueItem + 'a>(mut iters: Vec<RangeIter<'a, I>>) -> RangeIter<'a, I> {
+    if iters.len() == 1 {
+        iters.remove(0)
+    } else {
+        Box::new(MergedIter::new(iters))
+    }
+}
+
+/// Merge iterators over ranges of a table into a single iterator in ascending order of key and
+/// value. Items with an identical key and value are returned once.
+pub struct MergedIter<'a, I: OpaqueItem> {
+    iters: Vec<RangeIter<'a, I>>,
+    /// The next item of each iterator. `None` if the iterator has not been advanced, or is
+    /// exhausted.
+    heads: Vec<Option<I>>,
+    exhausted: Vec<bool>,
+    /// The key and value of the most recently returned item.
+    last: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<'a, I: OpaqueItem> MergedIter<'a, I> {
+    pub fn new(iters: Vec<RangeIter<'a, I>>) -> Self {
+        Self {
+            heads: iters.iter().map(|_| None).collect(),
+            exhausted: vec![false; iters.len()],
+            iters,
+            last: None,
+        }
+    }
+
+    fn next_item(&mut self) -> Result<Option<I>> {
+        loop {
+            for (i, iter) in self.iters.iter_mut().enumerate() {
+                if self.heads[i].is_some() || self.exhausted[i] {
+                    continue;
+                }
+                match iter.next() {
+                    Some(item) => self.heads[i] = Some(item?),
+                    None => self.exhausted[i] = true,
+                }
+            }
+            let min_i = self
+                .heads
+                .iter()
+                .enumerate()
+                .filter_map(|(i, head)| head.as_ref().map(|item| (i, item)))
+                .min_by(|(_, a), (_, b)| (a.key(), a.value()).cmp(&(b.key(), b.value())))
+                .map(|(i, _)| i);
+            let Some(min_i) = min_i else {
+                return Ok(None);
+            };
+            let item = self.heads[min_i].take().expect("head should be present");
+            if let Some((key, value)) = &self.last {
+                if key.as_slice() == item.key() && value.as_slice() == item.value() {
+                    continue;
+                }
+            }
+            self.last = Some((item.key().to_vec(), item.value().to_vec()));
+            return Ok(Some(item));
+        }
+    }
+}
+
+impl<I: OpaqueItem> Iterator for MergedIter<'_, I> {
+    type Item = Result<I>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_item().transpose()
+    }
+}
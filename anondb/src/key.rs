@@ -0,0 +1,57 @@
+use std::marker::PhantomData;
+
+use anondb_kv::*;
+
+/// The primary key type of a collection that does not declare one. It has no values, so `get`,
+/// `get_many` and `delete` cannot be called until the primary key type is declared as the last
+/// generic of the collection, e.g. `Collection<Document, K, u64>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UndeclaredKey {}
+
+/// Serializes a typed primary key to the same bytes the primary index produces for a document.
+pub type PrimaryKeySerializer<P> = fn(&P) -> Vec<u8>;
+
+/// Build a primary key serializer from the `PhantomData` accessors of the key fields (see
+/// `Queryable::DocumentPhantom`). A single field key is typed as the field itself, a compound key
+/// is typed as a tuple of the fields in primary key order.
+pub trait PrimaryKeyFields {
+    type Key;
+
+    fn serializer(&self) -> PrimaryKeySerializer<Self::Key>;
+}
+
+impl<A: SerializeLexicographic> PrimaryKeyFields for (PhantomData<A>,) {
+    type Key = A;
+
+    fn serializer(&self) -> PrimaryKeySerializer<Self::Key> {
+        |key| {
+            let mut out = LexicographicKey::default();
+            out.append_key_slice(key.serialize_lex().as_slice());
+            out.take()
+        }
+    }
+}
+
+macro_rules! primary_key_fields {
+    ($($field:ident $i:tt),+) => {
+        impl<$($field: SerializeLexicographic),+> PrimaryKeyFields for ($(PhantomData<$field>,)+) {
+            type Key = ($($field,)+);
+
+            fn serializer(&self) -> PrimaryKeySerializer<Self::Key> {
+                |key| {
+                    let mut out = LexicographicKey::default();
+                    $(out.append_key_slice(key.$i.serialize_lex().as_slice());)+
+                    out.take()
+                }
+            }
+        }
+    };
+}
+
+primary_key_fields!(A 0, B 1);
+primary_key_fields!(A 0, B 1, C 2);
+primary_key_fields!(A 0, B 1, C 2, D 3);
+primary_key_fields!(A 0, B 1, C 2, D 3, E 4);
+primary_key_fields!(A 0, B 1, C 2, D 3, E 4, F 5);
+primary_key_fields!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+primary_key_fields!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
@@ -1,14 +1,27 @@
+mod build;
 mod collection;
 mod index;
+mod iter;
+mod key;
 mod metadata;
+mod migration;
 mod query;
+mod snapshot;
+mod transaction;
 
+pub use build::*;
 pub use collection::*;
 pub use index::*;
-use metadata::*;
+pub use iter::*;
+pub use key::*;
+pub use metadata::*;
+pub use migration::*;
 pub use query::*;
+pub use snapshot::*;
+pub use transaction::*;
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default, clippy::get_first, unused_variables)]
 mod test;
 
 // re-exports
@@ -18,12 +31,16 @@ pub use anyhow;
 pub use anondb_kv::*;
 pub use anondb_macros::AnonDB;
 pub use anondb_macros::Document;
+pub use anondb_macros::SerializeLexicographic;
 
 pub trait Queryable {
     type DocumentQuery: Default;
     /// Used to access the types of fields by name. Accessors generated in
     /// anondb-macros/src/document.rs
     type DocumentPhantom;
+    /// Description of every stored field of the document, excluding `#[serde(skip)]` fields.
+    /// Generated in anondb-macros/src/document.rs
+    const FIELDS: &'static [FieldSchema];
 
     fn query() -> Self::DocumentQuery {
         Self::DocumentQuery::default()
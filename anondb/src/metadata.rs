@@ -1,5 +1,6 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::fmt;
 
 use anyhow::Result;
 use serde::Deserialize;
@@ -7,7 +8,17 @@ use serde::Serialize;
 
 use crate::*;
 
-#[derive(Serialize, Deserialize, PartialEq)]
+/// Reserved table storing the `MetadataDocument` of the database.
+pub const METADATA_TABLE: &str = "_anondb_metadata";
+/// Key of the `MetadataDocument` in the metadata table.
+const METADATA_KEY: &[u8] = b"metadata";
+/// Version of the `MetadataDocument` structure written by this implementation.
+pub const METADATA_VERSION: u64 = 0;
+/// Version of the encoding of document and index keys written by this implementation. Version 0
+/// terminated strings with a single 0x00 byte, without escaping 0x00 bytes within them.
+pub const KEY_FORMAT_VERSION: u64 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct IndexDescription {
     pub collection_name: String,
     pub field_names: Vec<String>,
@@ -16,47 +27,544 @@ pub struct IndexDescription {
     pub table_name: String,
 }
 
-#[derive(Serialize, Deserialize, PartialEq)]
+impl<T> From<&Index<T>> for IndexDescription
+where
+    T: Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    fn from(index: &Index<T>) -> Self {
+        Self {
+            collection_name: index.collection_name.clone(),
+            field_names: index
+                .field_names
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+            options: index.options.clone(),
+            table_name: index.table_name(),
+        }
+    }
+}
+
+impl IndexDescription {
+    /// Remove every entry of the index, along with its build state.
+    pub fn drop_table(&self, tx: &impl WriteTx) -> Result<()> {
+        if self.options.unique {
+            tx.clear(&self.table_name)?;
+        } else {
+            tx.clear_multimap(&self.table_name)?;
+        }
+        tx.remove(INDEX_BUILD_TABLE, self.table_name.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Static description of a document field. Generated in anondb-macros/src/document.rs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSchema {
+    /// Name of the field in the struct
+    pub name: &'static str,
+    /// Name of the field in stored documents, set with `#[serde(rename)]` or `#[serde(rename_all)]`
+    pub serialized_name: &'static str,
+    /// Type of the field as written in the struct
+    pub ty: &'static str,
+    /// The field is an `Option`
+    pub optional: bool,
+    /// The field may be missing from stored documents, set with `#[serde(default)]`
+    pub default: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldDescription {
+    pub ty: String,
+    pub optional: bool,
+    pub default: bool,
+}
+
+impl From<&FieldSchema> for FieldDescription {
+    fn from(field: &FieldSchema) -> Self {
+        Self {
+            ty: field.ty.to_string(),
+            optional: field.optional,
+            default: field.default,
+        }
+    }
+}
+
+impl FieldDescription {
+    /// Returns `true` if stored documents missing this field can be decoded.
+    pub fn is_backward_compatible(&self) -> bool {
+        self.optional || self.default
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct CollectionDescription {
-    pub fields: BTreeMap<String, String>,
+    /// Field names of the primary key, in key order
+    pub primary_key: Vec<String>,
+    /// Fields of the document keyed by serialized name
+    pub fields: BTreeMap<String, FieldDescription>,
+}
+
+/// A difference between the stored schema and the schema of the current instantiation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    CollectionAdded(String),
+    CollectionRemoved(String),
+    PrimaryKeyChanged {
+        collection: String,
+        from: Vec<String>,
+        to: Vec<String>,
+    },
+    FieldAdded {
+        collection: String,
+        field: String,
+        description: FieldDescription,
+    },
+    FieldRemoved {
+        collection: String,
+        field: String,
+    },
+    FieldChanged {
+        collection: String,
+        field: String,
+        from: FieldDescription,
+        to: FieldDescription,
+    },
+    IndexAdded(IndexDescription),
+    IndexRemoved(IndexDescription),
+    IndexChanged {
+        from: IndexDescription,
+        to: IndexDescription,
+    },
+    /// The stored keys use an older encoding, and every collection is rewritten when opened.
+    KeyFormatChanged {
+        from: u64,
+        to: u64,
+    },
+}
+
+impl SchemaChange {
+    /// The collection whose stored documents are affected by this change, if any.
+    pub fn document_collection(&self) -> Option<&str> {
+        match self {
+            Self::PrimaryKeyChanged { collection, .. }
+            | Self::FieldAdded { collection, .. }
+            | Self::FieldRemoved { collection, .. }
+            | Self::FieldChanged { collection, .. } => Some(collection),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the database can start with this change without intervention. Index
+    /// changes are applied when the database is opened, changes to documents need a migration.
+    pub fn is_compatible(&self) -> bool {
+        match self {
+            Self::CollectionAdded(_)
+            | Self::IndexAdded(_)
+            | Self::IndexRemoved(_)
+            | Self::IndexChanged { .. }
+            | Self::KeyFormatChanged { .. } => true,
+            Self::FieldAdded { description, .. } => description.is_backward_compatible(),
+            // only `#[serde(default)]` was added or removed
+            Self::FieldChanged { from, to, .. } => from.ty == to.ty && from.optional == to.optional,
+            Self::CollectionRemoved(_)
+            | Self::PrimaryKeyChanged { .. }
+            | Self::FieldRemoved { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CollectionAdded(name) => write!(f, "collection \"{name}\" added"),
+            Self::CollectionRemoved(name) => write!(f, "collection \"{name}\" removed"),
+            Self::PrimaryKeyChanged {
+                collection,
+                from,
+                to,
+            } => write!(
+                f,
+                "collection \"{collection}\" primary key changed from ({}) to ({})",
+                from.join(", "),
+                to.join(", ")
+            ),
+            Self::FieldAdded {
+                collection,
+                field,
+                description,
+            } => write!(
+                f,
+                "collection \"{collection}\" field \"{field}\" added with type {}{}",
+                description.ty,
+                if description.is_backward_compatible() {
+                    ""
+                } else {
+                    ", add #[serde(default)] or make it an Option"
+                }
+            ),
+            Self::FieldRemoved { collection, field } => {
+                write!(f, "collection \"{collection}\" field \"{field}\" removed")
+            }
+            Self::FieldChanged {
+                collection,
+                field,
+                from,
+                to,
+            } => write!(
+                f,
+                "collection \"{collection}\" field \"{field}\" changed from type {} to {}",
+                from.ty, to.ty
+            ),
+            Self::IndexAdded(index) => write!(f, "index \"{}\" added", index.table_name),
+            Self::IndexRemoved(index) => write!(f, "index \"{}\" removed", index.table_name),
+            Self::IndexChanged { from, to } => write!(
+                f,
+                "index \"{}\" changed to \"{}\"",
+                from.table_name, to.table_name
+            ),
+            Self::KeyFormatChanged { from, to } => {
+                write!(f, "key format changed from version {from} to {to}")
+            }
+        }
+    }
 }
 
 /// Represents metadata about an AnonDB instance. Tracks changes to collection document shapes and indices.
 ///
 /// Determines what work needs to be done on startup, and if stable startup is possible after
 /// schema changes.
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetadataDocument {
     pub version: u64,        // version of the metadata document itself
     pub schema_version: u64, // version of the database schema, this gets incremented when the
     // schema changes
-    pub indices_by_collection: HashMap<String, IndexDescription>,
+    pub indices_by_collection: HashMap<String, Vec<IndexDescription>>,
     pub collections: HashMap<String, CollectionDescription>,
+    /// Version of the key encoding, see `KEY_FORMAT_VERSION`. Metadata stored before the key
+    /// format was tracked loads as version 0.
+    #[serde(default)]
+    pub key_format: u64,
+}
+
+impl Default for MetadataDocument {
+    fn default() -> Self {
+        Self {
+            version: METADATA_VERSION,
+            schema_version: 0,
+            indices_by_collection: HashMap::default(),
+            collections: HashMap::default(),
+            key_format: KEY_FORMAT_VERSION,
+        }
+    }
 }
 
 impl MetadataDocument {
+    pub fn load(tx: &impl ReadOperations) -> Result<Option<Self>> {
+        match tx.get(METADATA_TABLE, METADATA_KEY)? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn store(&self, tx: &impl WriteTx) -> Result<()> {
+        tx.insert(
+            METADATA_TABLE,
+            METADATA_KEY,
+            &rmp_serde::to_vec_named(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// Compare against the stored metadata, and store this document if the schema changed or
+    /// migrations are pending. The first start stores the metadata unconditionally, at the latest
+    /// schema version of `migrations`. Refuses incompatible changes, leaving the stored metadata
+    /// untouched. This should be automatically invoked by the AnonDB proc macro.
+    ///
+    /// A database stored with an older key format is rewritten with the current one. A database
+    /// without metadata that contains documents predates it, and is rewritten as well.
+    ///
+    /// Tables of removed and changed indices are dropped, then `apply` is called in the same
+    /// write transaction with the pending migrations, to migrate documents and rebuild changed
+    /// indices and register added indices. If `apply` fails nothing is written. Added indices are
+    /// built by `Collection::build_indices_chunk`.
+    pub fn sync<K: KV>(
+        mut self,
+        kv: &K,
+        migrations: &Migrations,
+        apply: impl FnOnce(&K::WriteTransaction, &[SchemaChange], &[&Migration]) -> Result<()>,
+    ) -> Result<Vec<SchemaChange>> {
+        let stored = {
+            let tx = kv.read_tx()?;
+            Self::load(&tx)?
+        };
+        self.schema_version = migrations.schema_version();
+        let (changes, pending) = match stored {
+            Some(stored) => {
+                if stored.schema_version > self.schema_version {
+                    anyhow::bail!(
+                        "Database schema version {} is newer than the latest migration ({}), refusing to start",
+                        stored.schema_version,
+                        self.schema_version
+                    );
+                }
+                if stored.key_format > self.key_format {
+                    anyhow::bail!(
+                        "Database key format {} is newer than the supported key format ({}), refusing to start",
+                        stored.key_format,
+                        self.key_format
+                    );
+                }
+                let pending = migrations.pending(stored.schema_version);
+                let changes = self.compare(&stored, &pending)?;
+                if changes.is_empty() && pending.is_empty() {
+                    return Ok(changes);
+                }
+                (changes, pending)
+            }
+            None => {
+                let tx = kv.read_tx()?;
+                let mut changes = Vec::default();
+                for name in self.collections.keys() {
+                    if tx.count(name)? > 0 {
+                        changes.push(SchemaChange::KeyFormatChanged {
+                            from: 0,
+                            to: self.key_format,
+                        });
+                        break;
+                    }
+                }
+                (changes, Vec::default())
+            }
+        };
+        let tx = kv.write_tx()?;
+        for change in &changes {
+            match change {
+                SchemaChange::IndexRemoved(index)
+                | SchemaChange::IndexChanged { from: index, .. } => {
+                    index.drop_table(&tx)?;
+                }
+                _ => {}
+            }
+        }
+        apply(&tx, &changes, &pending)?;
+        self.store(&tx)?;
+        tx.commit()?;
+        Ok(changes)
+    }
+
     /// We'll compute a metadata document based on the current instantiation of AnonDB, and compare
-    /// with any previously stored metadata to determine schema changes.
-    pub fn compare(&self, other: &Self) -> Result<()> {
-        if self.version != other.version {
+    /// with any previously stored metadata to determine schema changes. Changes to the documents
+    /// of a collection are compatible if a `pending` migration rewrites the collection.
+    pub fn compare(&self, stored: &Self, pending: &[&Migration]) -> Result<Vec<SchemaChange>> {
+        if self.version != stored.version {
             anyhow::bail!(
                 "Metadata version mimatch. This implementation of AnonDB does not support metadata version migration."
             );
         }
-        if self.indices_by_collection == other.indices_by_collection
-            && self.collections == other.collections
-        {
-            return Ok(());
+        let changes = self.changes(stored);
+        let incompatible = changes
+            .iter()
+            .filter(|change| !change.is_compatible())
+            .filter(|change| {
+                change.document_collection().is_none_or(|collection| {
+                    !pending
+                        .iter()
+                        .any(|migration| migration.collection == collection)
+                })
+            })
+            .map(|change| format!("  - {change}"))
+            .collect::<Vec<_>>();
+        if !incompatible.is_empty() {
+            anyhow::bail!(
+                "Incompatible changes to schema detected, refusing to start:\n{}",
+                incompatible.join("\n")
+            );
+        }
+        Ok(changes)
+    }
+
+    /// List every difference between `stored` and this document, ordered by collection name
+    /// after a change of the key format.
+    pub fn changes(&self, stored: &Self) -> Vec<SchemaChange> {
+        let mut changes = Vec::default();
+        if self.key_format != stored.key_format {
+            changes.push(SchemaChange::KeyFormatChanged {
+                from: stored.key_format,
+                to: self.key_format,
+            });
         }
+        let mut collection_names = self
+            .collections
+            .keys()
+            .chain(stored.collections.keys())
+            .collect::<Vec<_>>();
+        collection_names.sort();
+        collection_names.dedup();
+        for name in collection_names {
+            let (current, previous) =
+                match (self.collections.get(name), stored.collections.get(name)) {
+                    (Some(current), Some(previous)) => (current, previous),
+                    (Some(_), None) => {
+                        changes.push(SchemaChange::CollectionAdded(name.clone()));
+                        continue;
+                    }
+                    (None, Some(_)) => {
+                        changes.push(SchemaChange::CollectionRemoved(name.clone()));
+                        continue;
+                    }
+                    (None, None) => unreachable!(),
+                };
+            if current.primary_key != previous.primary_key {
+                changes.push(SchemaChange::PrimaryKeyChanged {
+                    collection: name.clone(),
+                    from: previous.primary_key.clone(),
+                    to: current.primary_key.clone(),
+                });
+            }
+            // metadata stored before fields were described has no fields to compare against
+            if !previous.fields.is_empty() {
+                for (field, description) in &current.fields {
+                    match previous.fields.get(field) {
+                        Some(previous) if previous == description => {}
+                        Some(previous) => changes.push(SchemaChange::FieldChanged {
+                            collection: name.clone(),
+                            field: field.clone(),
+                            from: previous.clone(),
+                            to: description.clone(),
+                        }),
+                        None => changes.push(SchemaChange::FieldAdded {
+                            collection: name.clone(),
+                            field: field.clone(),
+                            description: description.clone(),
+                        }),
+                    }
+                }
+                for field in previous.fields.keys() {
+                    if !current.fields.contains_key(field) {
+                        changes.push(SchemaChange::FieldRemoved {
+                            collection: name.clone(),
+                            field: field.clone(),
+                        });
+                    }
+                }
+            }
 
-        // otherwise we have changes to the schema. We need to determine changes to indices and
-        // remove/rebuild them as necessary
-        //
-        // and examine changes to collection descriptions (documents). If the changes are purely
-        // additive we assume the user specifies a default impl for backward compat.
-        //
-        // If there are changes to the type of a field we need to reject it without a custom migration
+            let current_indices = self
+                .indices_by_collection
+                .get(name)
+                .cloned()
+                .unwrap_or_default();
+            let mut previous_indices = stored
+                .indices_by_collection
+                .get(name)
+                .cloned()
+                .unwrap_or_default();
+            for index in current_indices {
+                // an index over the same fields with different options is a change to that index
+                match previous_indices
+                    .iter()
+                    .position(|previous| previous.field_names == index.field_names)
+                {
+                    Some(i) => {
+                        let previous = previous_indices.remove(i);
+                        if previous != index {
+                            changes.push(SchemaChange::IndexChanged {
+                                from: previous,
+                                to: index,
+                            });
+                        }
+                    }
+                    None => changes.push(SchemaChange::IndexAdded(index)),
+                }
+            }
+            changes.extend(previous_indices.into_iter().map(SchemaChange::IndexRemoved));
+        }
+        changes
+    }
+}
 
-        anyhow::bail!("Changes to schema detected, refusing to start");
+impl<T, K: KV, PK> Collection<T, K, PK>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    /// Add the description of this collection and its indices to a metadata document. This
+    /// should be automatically invoked by the AnonDB proc macro.
+    pub fn describe(&self, metadata: &mut MetadataDocument) {
+        let primary_key = self
+            .primary_key_index()
+            .field_names
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect();
+        metadata.collections.insert(
+            self.name().to_string(),
+            CollectionDescription {
+                primary_key,
+                fields: T::FIELDS
+                    .iter()
+                    .map(|field| (field.serialized_name.to_string(), field.into()))
+                    .collect(),
+            },
+        );
+        metadata.indices_by_collection.insert(
+            self.name().to_string(),
+            self.indices()
+                .iter()
+                .map(|index| index.as_ref().into())
+                .collect(),
+        );
+    }
+
+    /// Migrate the documents of this collection if a `pending` migration targets it, or rewrite
+    /// them if the key format changed. Otherwise rebuild every index whose options changed,
+    /// checking unique constraints, and register the builds of added indices. This should be
+    /// automatically invoked by the AnonDB proc macro.
+    pub fn apply_schema_changes(
+        &self,
+        tx: &K::WriteTransaction,
+        changes: &[SchemaChange],
+        pending: &[&Migration],
+    ) -> Result<()> {
+        // migrations write every index
+        if self.migrate(tx, pending)? {
+            return Ok(());
+        }
+        if changes
+            .iter()
+            .any(|change| matches!(change, SchemaChange::KeyFormatChanged { .. }))
+        {
+            // as does rewriting the keys
+            return self.rewrite_keys(tx);
+        }
+        let collection_tx = CollectionTx::new(self, tx);
+        for change in changes {
+            let (to, is_added) = match change {
+                SchemaChange::IndexAdded(to) => (to, true),
+                SchemaChange::IndexChanged { to, .. } => (to, false),
+                _ => continue,
+            };
+            if to.collection_name != self.name() {
+                continue;
+            }
+            let Some(index) = self
+                .indices()
+                .iter()
+                .find(|index| index.table_name() == to.table_name)
+            else {
+                continue;
+            };
+            if is_added {
+                self.register_index_build(tx, index)?;
+                continue;
+            }
+            log::info!(
+                "Collection \"{}\" rebuilding changed index \"{}\"",
+                self.name(),
+                to.table_name
+            );
+            collection_tx.rebuild_index(index)?;
+        }
+        Ok(())
     }
 }
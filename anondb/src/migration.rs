@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::*;
+use anondb_kv::*;
+
+/// Maps the stored bytes of a document to the bytes of the document after a migration step.
+type MigrateFn = Box<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync>;
+
+/// A step that rewrites every document of a collection when the database is opened at an older
+/// schema version.
+pub struct Migration {
+    pub collection: String,
+    /// The schema version of the database after this step is applied.
+    pub schema_version: u64,
+    migrate: MigrateFn,
+}
+
+impl Migration {
+    /// Map the stored bytes of a single document.
+    pub fn apply(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        (self.migrate)(bytes)
+    }
+}
+
+impl std::fmt::Debug for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Migration")
+            .field("collection", &self.collection)
+            .field("schema_version", &self.schema_version)
+            .finish()
+    }
+}
+
+/// Ordered migration steps for the collections of a database. Passed to the `with_migrations`
+/// function generated by the AnonDB derive macro.
+///
+/// The schema version of the database is the highest version of any step. When a database with
+/// an older `MetadataDocument::schema_version` is opened, every newer step runs in order of
+/// version in the same write transaction that stores the new schema. Collections with pending
+/// steps may change document fields and the primary key. A new database starts at the latest
+/// version without running any steps.
+#[derive(Debug, Default)]
+pub struct Migrations {
+    steps: Vec<Migration>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a step that decodes stored documents as `Old`, usually a copy of the document struct
+    /// before the change, and maps them to `New`.
+    pub fn step<Old, New>(
+        mut self,
+        collection: &str,
+        schema_version: u64,
+        migrate: impl Fn(Old) -> Result<New> + Send + Sync + 'static,
+    ) -> Self
+    where
+        Old: for<'de> Deserialize<'de>,
+        New: Serialize,
+    {
+        self.steps.push(Migration {
+            collection: collection.to_string(),
+            schema_version,
+            migrate: Box::new(move |bytes: &[u8]| {
+                let document = migrate(rmp_serde::from_slice::<Old>(bytes)?)?;
+                Ok(rmp_serde::to_vec_named(&document)?)
+            }),
+        });
+        self
+    }
+
+    /// Add a step that maps stored documents as a `rmpv::Value`, for changes that do not need the
+    /// old document type. Documents are maps keyed by serialized field name.
+    pub fn value_step(
+        mut self,
+        collection: &str,
+        schema_version: u64,
+        migrate: impl Fn(rmpv::Value) -> Result<rmpv::Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.steps.push(Migration {
+            collection: collection.to_string(),
+            schema_version,
+            migrate: Box::new(move |mut bytes: &[u8]| {
+                let document = migrate(rmpv::decode::read_value(&mut bytes)?)?;
+                let mut out = Vec::default();
+                rmpv::encode::write_value(&mut out, &document)?;
+                Ok(out)
+            }),
+        });
+        self
+    }
+
+    /// The schema version of a database with every step applied.
+    pub fn schema_version(&self) -> u64 {
+        self.steps
+            .iter()
+            .map(|step| step.schema_version)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Steps newer than `schema_version`, in order of version.
+    pub fn pending(&self, schema_version: u64) -> Vec<&Migration> {
+        let mut pending = self
+            .steps
+            .iter()
+            .filter(|step| step.schema_version > schema_version)
+            .collect::<Vec<_>>();
+        pending.sort_by_key(|step| step.schema_version);
+        pending
+    }
+
+    /// Check that every step targets one of `collection_names`, and that no collection has two
+    /// steps with the same version. This should be automatically invoked by the AnonDB proc macro.
+    pub fn check(&self, collection_names: &[&str]) -> Result<()> {
+        let mut known_steps = HashSet::<(&str, u64)>::default();
+        for step in &self.steps {
+            if !collection_names.contains(&step.collection.as_str()) {
+                anyhow::bail!(
+                    "Migration to schema version {} targets unknown collection \"{}\"",
+                    step.schema_version,
+                    step.collection
+                );
+            }
+            // a new database is stored at version 0
+            if step.schema_version == 0 {
+                anyhow::bail!(
+                    "Collection \"{}\" migration schema version must be greater than 0",
+                    step.collection
+                );
+            }
+            if !known_steps.insert((step.collection.as_str(), step.schema_version)) {
+                anyhow::bail!(
+                    "Collection \"{}\" contains a duplicate migration to schema version {}",
+                    step.collection,
+                    step.schema_version
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T, K: KV, PK> Collection<T, K, PK>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    /// Apply the steps for this collection to every stored document, then write the documents
+    /// and all index entries again. The primary key of each document is computed from the
+    /// migrated document, and unique constraints are checked. All documents are loaded into
+    /// memory. Returns `false` if no step targets this collection.
+    pub fn migrate(&self, tx: &K::WriteTransaction, steps: &[&Migration]) -> Result<bool> {
+        let steps = steps
+            .iter()
+            .filter(|step| step.collection == self.name())
+            .collect::<Vec<_>>();
+        if steps.is_empty() {
+            return Ok(false);
+        }
+        let mut documents = Vec::default();
+        for item in tx.range(self.name(), ..)? {
+            let item = item?;
+            let mut bytes = item.value().to_vec();
+            for step in &steps {
+                bytes = step.apply(&bytes).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Collection \"{}\" migration to schema version {} failed: {e}",
+                        self.name(),
+                        step.schema_version
+                    )
+                })?;
+            }
+            documents.push(rmp_serde::from_slice::<T>(&bytes).map_err(|e| {
+                anyhow::anyhow!(
+                    "Collection \"{}\" cannot decode migrated document: {e}",
+                    self.name()
+                )
+            })?);
+        }
+        log::info!(
+            "Collection \"{}\" migrating {} documents to schema version {}",
+            self.name(),
+            documents.len(),
+            steps.last().unwrap().schema_version
+        );
+        self.rewrite(tx, &documents)?;
+        Ok(true)
+    }
+
+    /// Write every stored document and index entry again with the current key encoding. Used
+    /// when the database was stored with an older `MetadataDocument::key_format`. All documents
+    /// are loaded into memory.
+    pub fn rewrite_keys(&self, tx: &K::WriteTransaction) -> Result<()> {
+        let mut documents = Vec::default();
+        for item in tx.range(self.name(), ..)? {
+            documents.push(rmp_serde::from_slice::<T>(item?.value())?);
+        }
+        log::info!(
+            "Collection \"{}\" rewriting the keys of {} documents",
+            self.name(),
+            documents.len()
+        );
+        self.rewrite(tx, &documents)
+    }
+
+    /// Replace the documents of this collection with `documents`, and write every index again.
+    /// Unique constraints are checked.
+    fn rewrite(&self, tx: &K::WriteTransaction, documents: &[T]) -> Result<()> {
+        tx.clear(self.name())?;
+        for index in self.indices() {
+            let table_name = index.table_name();
+            if index.options.unique {
+                tx.clear(&table_name)?;
+            } else {
+                tx.clear_multimap(&table_name)?;
+            }
+            // every index is written below
+            IndexBuildState::complete().store(tx, &table_name)?;
+        }
+        let collection_tx = CollectionTx::new(self, tx);
+        for document in documents {
+            collection_tx.insert(document)?;
+        }
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ use anondb_kv::*;
 pub struct GeneralRange<T>(pub Bound<T>, pub Bound<T>);
 
 impl GeneralRange<Vec<u8>> {
-    pub fn as_slice<'a>(&'a self) -> GeneralRange<&'a [u8]> {
+    pub fn as_slice(&self) -> GeneralRange<&[u8]> {
         GeneralRange(
             self.0.as_ref().map(|v| v.as_slice()),
             self.1.as_ref().map(|v| v.as_slice()),
@@ -97,23 +97,23 @@ impl<T: PartialEq + PartialOrd> ParamTyped<T> {
     }
 }
 
-impl<T: SerializeLexicographic + PartialEq + PartialOrd> Into<Param> for ParamTyped<T> {
-    fn into(self) -> Param {
-        (&self).into()
+impl<T: SerializeLexicographic + PartialEq + PartialOrd> From<ParamTyped<T>> for Param {
+    fn from(value: ParamTyped<T>) -> Self {
+        (&value).into()
     }
 }
 
-impl<T: SerializeLexicographic + PartialEq + PartialOrd> Into<Param> for &ParamTyped<T> {
-    fn into(self) -> Param {
-        match self {
+impl<T: SerializeLexicographic + PartialEq + PartialOrd> From<&ParamTyped<T>> for Param {
+    fn from(value: &ParamTyped<T>) -> Self {
+        match value {
             ParamTyped::Eq(v) => Param::Eq(v.serialize_lex()),
             ParamTyped::Neq(v) => Param::Neq(v.serialize_lex()),
             ParamTyped::Range(v) => Param::Range(GeneralRange(
                 v.0.as_ref().map(|v| v.serialize_lex()),
                 v.1.as_ref().map(|v| v.serialize_lex()),
             )),
-            ParamTyped::In(v) => Param::In(v.into_iter().map(|v| v.serialize_lex()).collect()),
-            ParamTyped::Nin(v) => Param::Nin(v.into_iter().map(|v| v.serialize_lex()).collect()),
+            ParamTyped::In(v) => Param::In(v.iter().map(|v| v.serialize_lex()).collect()),
+            ParamTyped::Nin(v) => Param::Nin(v.iter().map(|v| v.serialize_lex()).collect()),
         }
     }
 }
@@ -171,7 +171,24 @@ macro_rules! eq_syntax {
         )+
     };
 }
-eq_syntax!(String, u8, u16, u32, u64, u128);
+eq_syntax!(
+    String,
+    Vec<u8>,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
 
 macro_rules! range_syntax {
     ($($type:ty),+) => {
@@ -230,7 +247,48 @@ macro_rules! range_syntax {
         )+
     };
 }
-range_syntax!(u8, u16, u32, u64, u128);
+range_syntax!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64
+);
+
+macro_rules! tuple_syntax {
+    ($($name:ident),+) => {
+        impl<$($name: PartialEq + PartialOrd + Clone),+> From<&($($name,)+)> for ParamTyped<($($name,)+)> {
+            fn from(value: &($($name,)+)) -> Self {
+                ParamTyped::Eq(value.clone())
+            }
+        }
+        impl<$($name: PartialEq + PartialOrd),+> From<($($name,)+)> for ParamTyped<($($name,)+)> {
+            fn from(value: ($($name,)+)) -> Self {
+                ParamTyped::Eq(value)
+            }
+        }
+        impl<$($name: PartialEq + PartialOrd),+> From<std::ops::Range<($($name,)+)>> for ParamTyped<($($name,)+)>
+        where
+            ($($name,)+): Clone,
+        {
+            fn from(value: std::ops::Range<($($name,)+)>) -> Self {
+                ParamTyped::Range(value.into())
+            }
+        }
+        impl<$($name: PartialEq + PartialOrd),+> From<std::ops::RangeInclusive<($($name,)+)>> for ParamTyped<($($name,)+)>
+        where
+            ($($name,)+): Clone,
+        {
+            fn from(value: std::ops::RangeInclusive<($($name,)+)>) -> Self {
+                ParamTyped::Range(value.into())
+            }
+        }
+    };
+}
+tuple_syntax!(A);
+tuple_syntax!(A, B);
+tuple_syntax!(A, B, C);
+tuple_syntax!(A, B, C, D);
+tuple_syntax!(A, B, C, D, E);
+tuple_syntax!(A, B, C, D, E, F);
+tuple_syntax!(A, B, C, D, E, F, G);
+tuple_syntax!(A, B, C, D, E, F, G, H);
 
 impl Param {
     pub fn eq<T: SerializeLexicographic>(val: T) -> Self {
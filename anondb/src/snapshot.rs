@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::*;
+use anondb_kv::*;
+
+/// A handle to a collection that performs every read against a shared read transaction. All
+/// queries see the same point in time view of the kv. Created by the `snapshot` function
+/// generated by the AnonDB derive macro.
+pub struct CollectionSnapshot<'s, T, K: KV, PK = UndeclaredKey>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    collection: &'s Collection<T, K, PK>,
+    tx: Arc<K::ReadTransaction>,
+}
+
+impl<'s, T, K: KV, PK> CollectionSnapshot<'s, T, K, PK>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    pub fn new(collection: &'s Collection<T, K, PK>, tx: Arc<K::ReadTransaction>) -> Self {
+        Self { collection, tx }
+    }
+
+    /// The collection this handle operates on.
+    pub fn collection(&self) -> &'s Collection<T, K, PK> {
+        self.collection
+    }
+
+    /// Return the number of documents in the collection.
+    pub fn count(&self) -> Result<u64> {
+        self.tx.count(self.collection.name())
+    }
+
+    /// Retrieve a document by primary key. See `Collection::get`.
+    pub fn get(&self, primary_key: &PK) -> Result<Option<T>> {
+        let key = self.collection.serialize_primary_key(primary_key);
+        match self.tx.get(self.collection.name(), key.as_slice())? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Retrieve many documents by primary key. See `Collection::get_many`.
+    pub fn get_many<'a>(
+        &self,
+        primary_keys: impl IntoIterator<Item = &'a PK>,
+    ) -> Result<Vec<Option<T>>>
+    where
+        PK: 'a,
+    {
+        primary_keys
+            .into_iter()
+            .map(|primary_key| self.get(primary_key))
+            .collect()
+    }
+
+    /// Lazily iterate over all documents matching a query. The iterator shares the snapshot and
+    /// may outlive this handle.
+    pub fn find_many(
+        &self,
+        query: T::DocumentQuery,
+    ) -> Result<DocumentIter<T, Arc<K::ReadTransaction>>> {
+        self.collection.find_many_in(self.tx.clone(), query)
+    }
+
+    pub fn find_one(&self, query: T::DocumentQuery) -> Result<Option<T>> {
+        self.find_many(query)?.next().transpose()
+    }
+}
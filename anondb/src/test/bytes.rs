@@ -0,0 +1,116 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Blob {
+    pub id: u64,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = name, id; unique = true)]
+    #[anondb(index = data)]
+    pub blobs: Collection<Blob, K>,
+}
+
+#[derive(AnonDB)]
+pub struct NameDB<K: KV> {
+    #[anondb(primary_key = name)]
+    #[anondb(index = group, name; unique = true)]
+    pub accounts: Collection<Account, K, String>,
+}
+
+/// Store the keys of `NameDB` in key format 0, where strings were terminated by a single 0x00
+/// byte.
+fn downgrade_key_format<K: KV>(kv: &K) -> Result<()> {
+    let tx = kv.write_tx()?;
+    let mut accounts = Vec::default();
+    for item in tx.range("accounts", ..)? {
+        let item = item?;
+        accounts.push((
+            rmp_serde::from_slice::<Account>(item.value())?,
+            item.value().to_vec(),
+        ));
+    }
+    tx.clear("accounts")?;
+    tx.clear("accounts_group_name_unique")?;
+    for (account, bytes) in accounts {
+        let primary_key = [account.name.as_bytes(), &[0x00]].concat();
+        let mut key = LexicographicKey::default();
+        key.append_key_slice(&account.group.serialize_lex());
+        key.append_key_slice(&primary_key);
+        tx.insert("accounts", &primary_key, &bytes)?;
+        tx.insert("accounts_group_name_unique", key.as_slice(), &primary_key)?;
+    }
+    let mut metadata = MetadataDocument::load(&tx)?.unwrap();
+    metadata.key_format = 0;
+    metadata.store(&tx)?;
+    tx.commit()
+}
+
+#[domacro(all_kv)]
+fn query_nul_bytes<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    let names = ["a", "a\0", "a\0b", "a\0\0", "ab", ""];
+    for (id, name) in names.iter().enumerate() {
+        db.blobs.insert(&Blob {
+            id: id as u64,
+            name: name.to_string(),
+            data: name.as_bytes().to_vec(),
+        })?;
+    }
+    for (id, name) in names.iter().enumerate() {
+        let found = db
+            .blobs
+            .find_many(Blob::query().name(*name))?
+            .map(|blob| blob.map(|blob| blob.id))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(found, vec![id as u64], "name {name:?}");
+        let found = db
+            .blobs
+            .find_many(Blob::query().data(name.as_bytes().to_vec()))?
+            .map(|blob| blob.map(|blob| blob.id))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(found, vec![id as u64], "data {name:?}");
+    }
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn rewrite_old_key_format<K: KV>() -> Result<()> {
+    let db = NameDB::<K>::in_memory(None)?;
+    for id in 0..8 {
+        db.accounts.insert(&account(id))?;
+    }
+    downgrade_key_format(db.kv().as_ref())?;
+
+    let db = NameDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    let metadata = MetadataDocument::load(&db.kv().read_tx()?)?.unwrap();
+    assert_eq!(metadata.key_format, KEY_FORMAT_VERSION);
+    assert_eq!(db.accounts.count()?, 8);
+    assert_eq!(db.kv().count("accounts_group_name_unique")?, 8);
+    assert!(db.index_build_progress()?.is_empty());
+    assert_eq!(db.accounts.get(&"account-5".to_string())?, Some(account(5)));
+    let found = db
+        .accounts
+        .find_one(Account::query().group(1).name("account-5".to_string()))?
+        .map(|account| account.id);
+    assert_eq!(found, Some(5));
+
+    // a key format newer than this implementation refuses to start
+    let tx = db.kv().write_tx()?;
+    let mut metadata = MetadataDocument::load(&tx)?.unwrap();
+    metadata.key_format = KEY_FORMAT_VERSION + 1;
+    metadata.store(&tx)?;
+    tx.commit()?;
+    let err = NameDB::<K>::in_memory(Some(&db.export_bytes()?))
+        .err()
+        .expect("should refuse to start with a newer key format");
+    assert!(
+        err.to_string()
+            .contains("is newer than the supported key format")
+    );
+    Ok(())
+}
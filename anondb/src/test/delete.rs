@@ -0,0 +1,56 @@
+use super::*;
+
+/// Assert that the collection and all index tables contain exactly `count` entries.
+fn assert_entries<K: KV>(db: &AccountDB<K>, count: u64) -> Result<()> {
+    assert_eq!(db.accounts.count()?, count);
+    assert_eq!(db.kv().count("accounts_name_unique")?, count);
+    assert_eq!(db.kv().count_multimap("accounts_group")?, count);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn delete<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        db.accounts.insert(&account(id))?;
+    }
+
+    assert_eq!(db.accounts.delete(&4u64)?, Some(account(4)));
+    assert_eq!(db.accounts.delete(&4u64)?, None);
+    assert_eq!(db.accounts.get(&4u64)?, None);
+    assert_entries(&db, 9)?;
+    assert!(
+        db.accounts
+            .find_one(Account::query().name("account-4"))?
+            .is_none()
+    );
+    assert_eq!(db.accounts.find_many(Account::query().group(1))?.count(), 3);
+
+    // the unique value is released
+    db.accounts.insert(&account(4))?;
+    assert_entries(&db, 10)?;
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn delete_query<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        db.accounts.insert(&account(id))?;
+    }
+
+    assert_eq!(
+        db.accounts.delete_one(Account::query().group(1))?,
+        Some(account(1))
+    );
+    assert_eq!(db.accounts.delete_one(Account::query().id(100))?, None);
+    assert_entries(&db, 9)?;
+
+    assert_eq!(db.accounts.delete_many(Account::query().group(1))?, 2);
+    assert_eq!(db.accounts.delete_many(Account::query().group(1))?, 0);
+    assert_entries(&db, 7)?;
+
+    assert_eq!(db.accounts.delete_many(Account::query())?, 7);
+    assert_entries(&db, 0)?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use super::*;
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, PartialOrd, SerializeLexicographic,
+)]
+pub enum Status {
+    Pending,
+    Active { since: u64 },
+    Closed(u32, bool),
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, PartialOrd, SerializeLexicographic,
+)]
+pub enum Visibility {
+    Public = 2,
+    Hidden = 1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, SerializeLexicographic)]
+#[repr(C, i8)]
+pub enum Level {
+    Warn(u8) = 3,
+    Debug = -1,
+    Info,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd, SerializeLexicographic)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd, SerializeLexicographic)]
+pub struct Tag(String, Option<u8>);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd, SerializeLexicographic)]
+pub struct Window {
+    pub start: u32,
+    pub end: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Lease {
+    pub id: u64,
+    pub window: Window,
+    pub holder: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Release {
+    pub id: u64,
+    pub status: Status,
+    pub version: Version,
+    pub compat: (u32, u32),
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = status)]
+    #[anondb(index = version; unique = true)]
+    #[anondb(index = compat, id)]
+    pub releases: Collection<Release, K>,
+}
+
+#[derive(AnonDB)]
+pub struct LeaseDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = window, holder)]
+    pub leases: Collection<Lease, K>,
+}
+
+#[test]
+fn should_encode_derived_types() {
+    let statuses = [
+        Status::Pending,
+        Status::Active { since: 0 },
+        Status::Active { since: 5 },
+        Status::Closed(0, false),
+        Status::Closed(0, true),
+        Status::Closed(7, false),
+    ];
+    for s0 in &statuses {
+        for s1 in &statuses {
+            assert_eq!(
+                s0.serialize_lex().partial_cmp(&s1.serialize_lex()),
+                s0.partial_cmp(s1)
+            );
+        }
+    }
+    assert_eq!(Status::Pending.serialize_lex(), vec![0]);
+    assert_eq!(<Status as SerializeLexicographic>::min(), vec![0]);
+    assert_eq!(
+        <Status as SerializeLexicographic>::max(),
+        Some(vec![2, 0xFF, 0xFF, 0xFF, 0xFF, 0x01])
+    );
+    // variants have different widths
+    assert_eq!(Status::fixed_width(), None);
+
+    // explicit discriminants are encoded instead of the variant index
+    assert!(Visibility::Hidden < Visibility::Public);
+    assert!(Visibility::Hidden.serialize_lex() < Visibility::Public.serialize_lex());
+    assert_eq!(Visibility::Hidden.serialize_lex(), 1isize.serialize_lex());
+    assert_eq!(
+        <Visibility as SerializeLexicographic>::min(),
+        Visibility::Hidden.serialize_lex()
+    );
+    assert_eq!(
+        <Visibility as SerializeLexicographic>::max(),
+        Some(Visibility::Public.serialize_lex())
+    );
+    assert_eq!(Visibility::fixed_width(), Some(8));
+
+    let levels = [Level::Debug, Level::Info, Level::Warn(0), Level::Warn(9)];
+    for l0 in &levels {
+        for l1 in &levels {
+            assert_eq!(
+                l0.serialize_lex().partial_cmp(&l1.serialize_lex()),
+                l0.partial_cmp(l1)
+            );
+        }
+    }
+    assert_eq!(Level::Info.serialize_lex(), 0i8.serialize_lex());
+    assert_eq!(
+        <Level as SerializeLexicographic>::min(),
+        Level::Debug.serialize_lex()
+    );
+    assert_eq!(
+        <Level as SerializeLexicographic>::max(),
+        Some(vec![0x83, 0xFF])
+    );
+
+    assert_eq!(Version::fixed_width(), Some(8));
+    assert_eq!(<Version as SerializeLexicographic>::min(), vec![0; 8]);
+    assert_eq!(
+        Version { major: 1, minor: 2 }.serialize_lex(),
+        vec![0, 0, 0, 1, 0, 0, 0, 2]
+    );
+
+    // an Option is one byte when None, so it never has a fixed width
+    assert_eq!(Window::fixed_width(), None);
+    assert!(
+        Window {
+            start: 1,
+            end: None
+        } < Window {
+            start: 1,
+            end: Some(0)
+        }
+    );
+    assert!(
+        Window {
+            start: 1,
+            end: None
+        }
+        .serialize_lex()
+            < Window {
+                start: 1,
+                end: Some(0)
+            }
+            .serialize_lex()
+    );
+
+    assert_eq!(Tag::fixed_width(), None);
+    assert_eq!(<Tag as SerializeLexicographic>::max(), None);
+    let tags = [
+        Tag("a".into(), None),
+        Tag("a".into(), Some(0)),
+        Tag("a\0".into(), None),
+        Tag("b".into(), Some(1)),
+    ];
+    for t0 in &tags {
+        for t1 in &tags {
+            assert_eq!(
+                t0.serialize_lex().partial_cmp(&t1.serialize_lex()),
+                t0.partial_cmp(t1)
+            );
+        }
+    }
+}
+
+#[domacro(all_kv)]
+fn query_derived<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    let statuses = [
+        Status::Pending,
+        Status::Active { since: 10 },
+        Status::Closed(3, true),
+    ];
+    for id in 0..9u64 {
+        db.releases.insert(&Release {
+            id,
+            status: statuses[id as usize % 3],
+            version: Version {
+                major: id as u32 / 3,
+                minor: id as u32 % 3,
+            },
+            compat: (id as u32 % 2, id as u32),
+        })?;
+    }
+
+    let ids = |query| -> Result<Vec<u64>> {
+        db.releases
+            .find_many(query)?
+            .map(|release| release.map(|release| release.id))
+            .collect()
+    };
+    assert_eq!(
+        ids(Release::query().status(Status::Active { since: 10 }))?,
+        vec![1, 4, 7]
+    );
+    assert_eq!(
+        ids(Release::query()
+            .version(Version { major: 1, minor: 1 }..Version { major: 2, minor: 1 }))?,
+        vec![4, 5, 6]
+    );
+    assert_eq!(ids(Release::query().compat((1, 5)))?, vec![5]);
+    assert_eq!(
+        ids(Release::query().compat((0, 2)..=(0, 6)))?,
+        vec![2, 4, 6]
+    );
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn query_option_fields<K: KV>() -> Result<()> {
+    let db = LeaseDB::<K>::in_memory(None)?;
+    for id in 0..6u64 {
+        db.leases.insert(&Lease {
+            id,
+            window: Window {
+                start: id as u32 % 2,
+                end: if id % 3 == 0 { None } else { Some(id as u32) },
+            },
+            holder: id as u32 % 3,
+        })?;
+    }
+    // the window is skipped, so the planner must not assume it has a fixed width
+    let ids = db
+        .leases
+        .find_many(Lease::query().holder(0))?
+        .map(|lease| lease.map(|lease| lease.id))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(ids, vec![0, 3]);
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Event {
+    pub id: u64,
+    pub kind: u8,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = kind)]
+    pub events: Collection<Event, K>,
+}
+
+#[domacro(all_kv)]
+fn find_many_lazy<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for id in 0..1000 {
+        db.events.insert(&Event {
+            id,
+            kind: (id % 2) as u8,
+        })?;
+    }
+
+    // primary index
+    let first = db
+        .events
+        .find_many(Event::query().id(100..))?
+        .take(10)
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        first.iter().map(|e| e.id).collect::<Vec<_>>(),
+        (100..110).collect::<Vec<_>>()
+    );
+
+    // multimap index
+    let first = db
+        .events
+        .find_many(Event::query().kind(1))?
+        .take(3)
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(
+        first.iter().map(|e| e.id).collect::<Vec<_>>(),
+        vec![1, 3, 5]
+    );
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn find_many_snapshot<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        db.events.insert(&Event { id, kind: 0 })?;
+    }
+    let mut docs = db.events.find_many(Event::query().kind(0))?;
+    assert_eq!(docs.next().transpose()?.map(|e| e.id), Some(0));
+    // documents inserted after the iterator is created are not visible to it
+    for id in 10..20 {
+        db.events.insert(&Event { id, kind: 0 })?;
+    }
+    assert_eq!(docs.count(), 9);
+    assert_eq!(db.events.find_many(Event::query().kind(0))?.count(), 20);
+    Ok(())
+}
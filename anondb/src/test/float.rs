@@ -0,0 +1,45 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Point {
+    pub id: u64,
+    pub elevation: f32,
+    pub speed: f64,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = speed)]
+    #[anondb(index = elevation)]
+    pub points: Collection<Point, K>,
+}
+
+#[domacro(all_kv)]
+fn query_floats<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for id in 0..20u64 {
+        db.points.insert(&Point {
+            id,
+            elevation: id as f32 * -2.5,
+            speed: id as f64 * 1.25,
+        })?;
+    }
+
+    let ids = |query| -> Result<Vec<u64>> {
+        db.points
+            .find_many(query)?
+            .map(|point| point.map(|point| point.id))
+            .collect()
+    };
+    // speeds 5.0, 6.25, 7.5, 8.75, 10.0, 11.25
+    assert_eq!(
+        ids(Point::query().speed(5.0..12.5))?,
+        vec![4, 5, 6, 7, 8, 9]
+    );
+    assert_eq!(ids(Point::query().speed(23.75))?, vec![19]);
+    // ascending elevation, most negative first
+    assert_eq!(ids(Point::query().elevation(-10.0..=-5.0))?, vec![4, 3, 2]);
+    assert_eq!(ids(Point::query().elevation(-0.0))?, vec![0]);
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use super::*;
+
+#[derive(AnonDB)]
+pub struct OldDB<K: KV> {
+    #[anondb(primary_key = id)]
+    pub accounts: Collection<Account, K, u64>,
+}
+
+/// Remove the build states, as stored by a database that predates them.
+fn clear_build_states<K: KV>(kv: &K) -> Result<()> {
+    let tx = kv.write_tx()?;
+    tx.clear(INDEX_BUILD_TABLE)?;
+    tx.commit()
+}
+
+#[domacro(all_kv)]
+fn build_in_chunks<K: KV>() -> Result<()> {
+    let old_db = OldDB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        old_db.accounts.insert(&account(id))?;
+    }
+    let db = AccountDB::<K>::in_memory(Some(&old_db.export_bytes()?))?;
+    let progress = db.index_build_progress()?;
+    assert_eq!(progress.len(), 2);
+    assert!(progress.iter().all(|p| p.processed == 0 && p.total == 10));
+
+    // the planner ignores incomplete indices, queries still answer correctly
+    assert_eq!(db.accounts.find_many(Account::query().group(1))?.count(), 3);
+
+    assert!(!db.accounts.build_indices_chunk(4)?);
+    let progress = db.index_build_progress()?;
+    assert_eq!(progress[0].index, "accounts_name_unique");
+    assert_eq!(progress[0].processed, 4);
+
+    // writes during the build are indexed
+    db.accounts.insert(&account(10))?;
+    db.accounts.delete(&0u64)?;
+
+    // resume from the persisted cursor after reopening
+    let db = AccountDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    let progress = db.index_build_progress()?;
+    assert_eq!(progress[0].processed, 4);
+    assert_eq!(progress[0].total, 10);
+
+    db.build_indices(3)?;
+    assert!(db.index_build_progress()?.is_empty());
+    assert_eq!(db.kv().count("accounts_name_unique")?, 10);
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 10);
+    let ids = db
+        .accounts
+        .find_many(Account::query().group(2))?
+        .map(|account| account.map(|account| account.id))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(ids, vec![2, 6, 10]);
+
+    // completed builds stay complete after reopening
+    let db = AccountDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    assert!(db.index_build_progress()?.is_empty());
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn build_empty_collection<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    assert!(db.index_build_progress()?.is_empty());
+    assert!(db.accounts.build_indices_chunk(1)?);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn keep_untracked_indices<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        db.accounts.insert(&account(id))?;
+    }
+    clear_build_states(db.kv().as_ref())?;
+
+    // populated indices are complete, and are not cleared
+    let db = AccountDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    assert!(db.index_build_progress()?.is_empty());
+    assert_eq!(db.kv().count("accounts_name_unique")?, 10);
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 10);
+    assert_eq!(db.accounts.find_many(Account::query().group(1))?.count(), 3);
+
+    // empty indices over existing documents must be built
+    clear_build_states(db.kv().as_ref())?;
+    let tx = db.kv().write_tx()?;
+    tx.clear_multimap("accounts_group")?;
+    tx.commit()?;
+    let db = AccountDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    let progress = db.index_build_progress()?;
+    assert_eq!(progress.len(), 1);
+    assert_eq!(progress[0].index, "accounts_group");
+    db.build_indices(4)?;
+    assert!(db.index_build_progress()?.is_empty());
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 10);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn fail_unique_violation<K: KV>() -> Result<()> {
+    let old_db = OldDB::<K>::in_memory(None)?;
+    for id in 0..4 {
+        old_db.accounts.insert(&account(id))?;
+    }
+    old_db.accounts.insert(&Account {
+        id: 4,
+        name: "account-1".into(),
+        group: 0,
+    })?;
+    let db = AccountDB::<K>::in_memory(Some(&old_db.export_bytes()?))?;
+
+    let err = db
+        .build_indices(2)
+        .expect_err("should fail to build a violated unique index");
+    assert!(err.to_string().contains("uniqueness constraint violated"));
+    let progress = db.index_build_progress()?;
+    assert_eq!(progress[0].index, "accounts_name_unique");
+    assert_eq!(progress[0].processed, 4);
+    assert!(progress[0].failed.is_some());
+
+    // the failed build is not retried, other indices are still built
+    let err = db
+        .build_indices(2)
+        .expect_err("should report the failed build");
+    assert!(
+        err.to_string()
+            .contains("Collection \"accounts\" index \"accounts_name_unique\" build failed")
+    );
+    let progress = db.index_build_progress()?;
+    assert_eq!(progress.len(), 1);
+    assert_eq!(progress[0].index, "accounts_name_unique");
+
+    // the failure persists, rebuilding once the documents are fixed completes the index
+    let db = AccountDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    assert!(db.index_build_progress()?[0].failed.is_some());
+    db.accounts.delete(&4u64)?;
+    db.accounts.rebuild_indices()?;
+    assert!(db.index_build_progress()?.is_empty());
+    assert_eq!(db.kv().count("accounts_name_unique")?, 4);
+    db.build_indices(2)?;
+    Ok(())
+}
@@ -41,3 +41,72 @@ fn should_insert_document() -> Result<()> {
 
     Ok(())
 }
+
+#[domacro(all_kv)]
+fn insert_many_atomic<K: KV>() -> Result<()> {
+    let db = ItemDB::<K>::in_memory(None)?;
+    let items = (0..100).map(item).collect::<Vec<_>>();
+    let report = db.items.insert_many(&items, InsertMode::Atomic)?;
+    assert_eq!(report.inserted, 100);
+    assert!(report.skipped.is_empty());
+    assert_eq!(db.items.count()?, 100);
+    assert_eq!(db.items.get(&42u64)?, Some(item(42)));
+
+    // a violation anywhere in the input inserts nothing
+    let items = [item(100), item(101), item(5)];
+    db.items
+        .insert_many(&items, InsertMode::Atomic)
+        .expect_err("should fail to insert a duplicate primary key");
+    let items = [
+        item(100),
+        Item {
+            name: "item-100".into(),
+            ..item(101)
+        },
+    ];
+    db.items
+        .insert_many(&items, InsertMode::Atomic)
+        .expect_err("should fail to insert duplicates within the input");
+    assert_eq!(db.items.count()?, 100);
+    assert_eq!(db.items.get(&100u64)?, None);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn insert_many_skip<K: KV>() -> Result<()> {
+    let db = ItemDB::<K>::in_memory(None)?;
+    db.items.insert(&item(0))?;
+    let items = [
+        item(1),
+        item(0),
+        Item {
+            name: "item-1".into(),
+            ..item(2)
+        },
+        item(3),
+        item(3),
+    ];
+    let report = db.items.insert_many(&items, InsertMode::SkipDuplicates)?;
+    assert_eq!(report.inserted, 2);
+    assert_eq!(
+        report.skipped,
+        vec![
+            SkippedDocument {
+                position: 1,
+                index: "items".into(),
+            },
+            SkippedDocument {
+                position: 2,
+                index: "items_name_unique".into(),
+            },
+            SkippedDocument {
+                position: 4,
+                index: "items".into(),
+            },
+        ]
+    );
+    assert_eq!(db.items.count()?, 3);
+    assert_eq!(db.kv().count("items_name_unique")?, 3);
+    assert_eq!(db.items.get(&2u64)?, None);
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct AccountV0 {
+    pub id: u64,
+    pub name: String,
+    pub group: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct AccountV1 {
+    pub id: u64,
+    pub name: String,
+    pub group: u16,
+    pub balance: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Account {
+    pub id: u64,
+    pub handle: String,
+    pub group: u16,
+    pub balance: u64,
+}
+
+#[derive(AnonDB)]
+pub struct V0DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group)]
+    pub accounts: Collection<AccountV0, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = handle)]
+    #[anondb(index = group)]
+    #[anondb(index = id; unique = true)]
+    pub accounts: Collection<Account, K, String>,
+}
+
+#[derive(AnonDB)]
+pub struct UniqueGroupDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group; unique = true)]
+    pub accounts: Collection<AccountV1, K, u64>,
+}
+
+fn account(id: u64) -> AccountV0 {
+    AccountV0 {
+        id,
+        name: format!("account-{id}"),
+        group: (id % 2) as u8,
+    }
+}
+
+/// Widen `group` and add `balance` at version 1, then rename `name` to `handle` at version 2. The
+/// steps are registered out of order.
+fn migrations() -> Migrations {
+    Migrations::new()
+        .value_step("accounts", 2, |value| {
+            let rmpv::Value::Map(entries) = value else {
+                anyhow::bail!("expected a map");
+            };
+            Ok(rmpv::Value::Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| match key.as_str() {
+                        Some("name") => ("handle".into(), value),
+                        _ => (key, value),
+                    })
+                    .collect(),
+            ))
+        })
+        .step("accounts", 1, |account: AccountV0| {
+            Ok(AccountV1 {
+                id: account.id,
+                name: account.name,
+                group: account.group.into(),
+                balance: 0,
+            })
+        })
+}
+
+#[domacro(all_kv)]
+fn migrate_documents<K: KV>() -> Result<()> {
+    let db = V0DB::<K>::in_memory(None)?;
+    for id in 0..4 {
+        db.accounts.insert(&account(id))?;
+    }
+    let bytes = db.export_bytes()?;
+
+    let err = DB::<K>::in_memory(Some(&bytes))
+        .err()
+        .expect("should refuse to start without a migration");
+    assert!(err.to_string().contains("Incompatible changes to schema"));
+
+    let db = DB::<K>::with_migrations(K::in_memory(Some(&bytes))?, &migrations())?;
+    assert_eq!(db.accounts.count()?, 4);
+    // documents are stored under the new primary key
+    assert_eq!(
+        db.accounts.get(&"account-2".to_string())?,
+        Some(Account {
+            id: 2,
+            handle: "account-2".into(),
+            group: 0,
+            balance: 0,
+        })
+    );
+    // every index is rebuilt and usable
+    assert!(db.index_build_progress()?.is_empty());
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 4);
+    assert_eq!(db.kv().count("accounts_id_unique")?, 4);
+    assert_eq!(db.accounts.find_many(Account::query().group(1))?.count(), 2);
+    let metadata = MetadataDocument::load(&db.kv().read_tx()?)?.unwrap();
+    assert_eq!(metadata.schema_version, 2);
+
+    // steps only run once
+    let bytes = db.export_bytes()?;
+    let db = DB::<K>::with_migrations(K::in_memory(Some(&bytes))?, &migrations())?;
+    assert_eq!(db.accounts.count()?, 4);
+
+    let err = DB::<K>::in_memory(Some(&bytes))
+        .err()
+        .expect("should refuse to start without the migrations");
+    assert!(
+        err.to_string()
+            .contains("Database schema version 2 is newer than the latest migration (0)")
+    );
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn start_at_latest_version<K: KV>() -> Result<()> {
+    let db = DB::<K>::with_migrations(K::in_memory(None)?, &migrations())?;
+    let metadata = MetadataDocument::load(&db.kv().read_tx()?)?.unwrap();
+    assert_eq!(metadata.schema_version, 2);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn refuse_failed_migration<K: KV>() -> Result<()> {
+    let db = V0DB::<K>::in_memory(None)?;
+    for id in 0..4 {
+        db.accounts.insert(&account(id))?;
+    }
+    let bytes = db.export_bytes()?;
+
+    let migrations = Migrations::new().step("accounts", 1, |account: AccountV0| {
+        if account.id == 3 {
+            anyhow::bail!("account 3 cannot be migrated");
+        }
+        Ok(AccountV1 {
+            id: account.id,
+            name: account.name,
+            group: account.group.into(),
+            balance: 0,
+        })
+    });
+    let err = UniqueGroupDB::<K>::with_migrations(K::in_memory(Some(&bytes))?, &migrations)
+        .err()
+        .expect("should refuse to start with a failed migration");
+    assert!(err.to_string().contains(
+        "Collection \"accounts\" migration to schema version 1 failed: account 3 cannot be migrated"
+    ));
+
+    // migrated documents are checked against unique indices
+    let migrations = Migrations::new().step("accounts", 1, |account: AccountV0| {
+        Ok(AccountV1 {
+            id: account.id,
+            name: account.name,
+            group: account.group.into(),
+            balance: 0,
+        })
+    });
+    let err = UniqueGroupDB::<K>::with_migrations(K::in_memory(Some(&bytes))?, &migrations)
+        .err()
+        .expect("should refuse to start with a unique constraint violation");
+    assert!(err.to_string().contains("uniqueness constraint violated"));
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn refuse_invalid_migrations<K: KV>() -> Result<()> {
+    let migrate = |account: AccountV0| Ok(account);
+    let err = V0DB::<K>::with_migrations(
+        K::in_memory(None)?,
+        &Migrations::new().step("users", 1, migrate),
+    )
+    .err()
+    .expect("should refuse a migration of an unknown collection");
+    assert!(
+        err.to_string()
+            .contains("Migration to schema version 1 targets unknown collection \"users\"")
+    );
+    let err = V0DB::<K>::with_migrations(
+        K::in_memory(None)?,
+        &Migrations::new()
+            .step("accounts", 1, migrate)
+            .step("accounts", 1, migrate),
+    )
+    .err()
+    .expect("should refuse duplicate migrations");
+    assert!(
+        err.to_string()
+            .contains("Collection \"accounts\" contains a duplicate migration to schema version 1")
+    );
+    Ok(())
+}
+
+#[test]
+fn should_leave_store_unchanged_on_failed_migration() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("anondb-{}.redb", rand::random::<u64>()));
+    {
+        let db = V0DB::<RedbKV>::at_path(&path)?;
+        db.accounts.insert(&account(0))?;
+        db.accounts.insert(&account(2))?;
+    }
+    let migrations = Migrations::new().step("accounts", 1, |account: AccountV0| {
+        Ok(AccountV1 {
+            id: account.id,
+            name: account.name,
+            group: account.group.into(),
+            balance: 0,
+        })
+    });
+    UniqueGroupDB::<RedbKV>::with_migrations(RedbKV::at_path(&path)?, &migrations)
+        .err()
+        .expect("should refuse to start with a unique constraint violation");
+    {
+        let db = V0DB::<RedbKV>::at_path(&path)?;
+        assert_eq!(db.accounts.get(&2)?, Some(account(2)));
+        let metadata = MetadataDocument::load(&db.kv().read_tx()?)?.unwrap();
+        assert_eq!(metadata.schema_version, 0);
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
@@ -66,7 +66,7 @@ fn should_use_non_unique_index() -> Result<()> {
     let out = db
         .other_collection
         .find_many(OtherDocument::query().other(0..))?
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>>>()?;
     assert_eq!(out.len(), 2);
     assert_eq!(out.get(0).unwrap().other, 99);
     assert_eq!(out.get(1).unwrap().other, 200);
@@ -107,3 +107,59 @@ fn should_query_collection() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn should_persist_at_path() -> Result<()> {
+    let path = std::env::temp_dir().join(format!("anondb-{}.redb", rand::random::<u64>()));
+    let doc = OtherDocument { id: 99, other: 99 };
+    {
+        let db = DB::<RedbKV>::at_path(&path)?;
+        db.other_collection.insert(&doc)?;
+    }
+    {
+        let db = DB::<RedbKV>::from_kv(RedbKV::at_path_with_options(
+            &path,
+            RedbOptions {
+                reject_writes: true,
+                ..Default::default()
+            },
+        )?)?;
+        assert_eq!(
+            db.other_collection
+                .find_one(OtherDocument::query().id(99))?,
+            Some(doc)
+        );
+    }
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn export_and_import<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for i in 0..10 {
+        db.test_collection.insert(&TestDocument2 {
+            id: i,
+            other: format!("doc {i}"),
+        })?;
+        db.other_collection
+            .insert(&OtherDocument { id: i, other: 5 })?;
+    }
+
+    let db2 = DB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    assert_eq!(db2.test_collection.count()?, 10);
+    assert_eq!(
+        db2.test_collection.find_one(TestDocument2::query().id(3))?,
+        Some(TestDocument2 {
+            id: 3,
+            other: "doc 3".into(),
+        })
+    );
+    assert_eq!(
+        db2.other_collection
+            .find_many(OtherDocument::query().other(5))?
+            .count(),
+        10
+    );
+    Ok(())
+}
@@ -1,15 +1,56 @@
+mod bytes;
+mod delete;
+mod derive_lex;
+mod find_many;
+mod float;
+mod index_build;
 mod insert;
+mod migration;
 mod misc;
+mod param_in;
+mod param_neq;
 mod primary_key;
 mod range;
+mod rebuild;
+mod schema;
+mod signed;
+mod snapshot;
+mod transaction;
 mod unique_index;
+mod update;
 
 use anyhow::Result;
+use domacro::domacro;
 use serde::Deserialize;
 use serde::Serialize;
 
 use crate::*;
 
+/// Run a test function generic over `K: KV` against every kv implementation. Used as
+/// `#[domacro(all_kv)]` on the test function.
+#[macro_export]
+macro_rules! all_kv {
+    ($fn_name:ident) => {
+        paste::paste! {
+            #[test]
+            fn [<mem_ $fn_name>]() -> Result<()> {
+                $fn_name::<MemKV>()
+            }
+
+            #[test]
+            fn [<redb_ $fn_name>]() -> Result<()> {
+                $fn_name::<RedbKV>()
+            }
+
+            #[cfg(feature = "fjall")]
+            #[test]
+            fn [<fjall_ $fn_name>]() -> Result<()> {
+                $fn_name::<FjallKV>()
+            }
+        }
+    };
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
 pub struct TestDocument {
     pub id0: u128,
@@ -36,3 +77,52 @@ impl Default for TestDocument {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Item {
+    pub id: u64,
+    pub name: String,
+    pub group: u8,
+    pub score: u32,
+}
+
+#[derive(AnonDB)]
+pub struct ItemDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = name; unique = true)]
+    #[anondb(index = group)]
+    #[anondb(index = group, score)]
+    pub items: Collection<Item, K, u64>,
+}
+
+pub fn item(id: u64) -> Item {
+    Item {
+        id,
+        name: format!("item-{id}"),
+        group: (id % 5) as u8,
+        score: (id * 7 % 40) as u32,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Account {
+    pub id: u64,
+    pub name: String,
+    pub group: u8,
+}
+
+#[derive(AnonDB)]
+pub struct AccountDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = name; unique = true)]
+    #[anondb(index = group)]
+    pub accounts: Collection<Account, K, u64>,
+}
+
+pub fn account(id: u64) -> Account {
+    Account {
+        id,
+        name: format!("account-{id}"),
+        group: (id % 4) as u8,
+    }
+}
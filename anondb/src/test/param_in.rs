@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use super::*;
+
+fn setup<K: KV>() -> Result<(Arc<ItemDB<K>>, Vec<Item>)> {
+    let db = ItemDB::<K>::in_memory(None)?;
+    let items = (0..100).map(item).collect::<Vec<_>>();
+    for item in &items {
+        db.items.insert(item)?;
+    }
+    Ok((db, items))
+}
+
+fn ids(docs: impl Iterator<Item = Result<Item>>) -> Result<Vec<u64>> {
+    docs.map(|doc| doc.map(|doc| doc.id)).collect()
+}
+
+#[domacro(all_kv)]
+fn in_primary_key<K: KV>() -> Result<()> {
+    let (db, _) = setup::<K>()?;
+    let found = ids(db
+        .items
+        .find_many(Item::query().id(ParamTyped::In(vec![5, 3, 3, 900])))?)?;
+    assert_eq!(found, vec![3, 5]);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn in_index<K: KV>() -> Result<()> {
+    let (db, items) = setup::<K>()?;
+    let found = db
+        .items
+        .find_many(Item::query().group(ParamTyped::In(vec![2, 0, 2])))?
+        .collect::<Result<Vec<_>>>()?;
+    // results are in index key order, so grouped by ascending group
+    assert!(found.windows(2).all(|w| w[0].group <= w[1].group));
+    let mut found = found.iter().map(|item| item.id).collect::<Vec<_>>();
+    found.sort();
+    let expected = items
+        .iter()
+        .filter(|item| item.group == 0 || item.group == 2)
+        .map(|item| item.id)
+        .collect::<Vec<_>>();
+    assert_eq!(found, expected);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn in_with_range<K: KV>() -> Result<()> {
+    let (db, items) = setup::<K>()?;
+    let found = ids(db.items.find_many(
+        Item::query()
+            .group(ParamTyped::In(vec![3, 1]))
+            .score(10..20),
+    )?)?;
+    let mut expected = items.clone();
+    expected.retain(|item| [1, 3].contains(&item.group) && (10..20).contains(&item.score));
+    expected.sort_by_key(|item| (item.group, item.score, item.id));
+    assert!(!expected.is_empty());
+    assert_eq!(
+        found,
+        expected.iter().map(|item| item.id).collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn in_empty<K: KV>() -> Result<()> {
+    let (db, _) = setup::<K>()?;
+    let found = ids(db
+        .items
+        .find_many(Item::query().group(ParamTyped::In(vec![])))?)?;
+    assert!(found.is_empty());
+    Ok(())
+}
+
+#[test]
+fn should_merge_overlapping_ranges() -> Result<()> {
+    use std::ops::Bound;
+
+    let kv = MemKV::in_memory(None)?;
+    for i in 0..10u8 {
+        kv.insert_multimap("table", &[i], &[0])?;
+        kv.insert_multimap("table", &[i], &[1])?;
+    }
+    let tx = kv.read_tx()?;
+    let iters = vec![
+        tx.range_multimap_detached(
+            "table",
+            (Bound::Included(vec![5]), Bound::Unbounded),
+            SortDirection::Asc,
+        )?,
+        tx.range_multimap_detached(
+            "table",
+            (Bound::Unbounded, Bound::Included(vec![6])),
+            SortDirection::Asc,
+        )?,
+    ];
+    let merged = MergedIter::new(iters)
+        .map(|item| item.map(|item| (item.key()[0], item.value()[0])))
+        .collect::<Result<Vec<_>>>()?;
+    let expected = (0..10u8).flat_map(|i| [(i, 0), (i, 1)]).collect::<Vec<_>>();
+    assert_eq!(merged, expected);
+    Ok(())
+}
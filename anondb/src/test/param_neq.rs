@@ -0,0 +1,205 @@
+use std::ops::Bound;
+use std::sync::Arc;
+
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Task {
+    pub id: u64,
+    pub status: String,
+    pub group: u8,
+    pub score: u32,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = status)]
+    #[anondb(index = group, score)]
+    pub tasks: Collection<Task, K>,
+}
+
+const STATUSES: [&str; 4] = ["active", "deleted", "done", "draft"];
+
+fn setup<K: KV>() -> Result<(Arc<DB<K>>, Vec<Task>)> {
+    let db = DB::<K>::in_memory(None)?;
+    let mut tasks = Vec::default();
+    for id in 0..100 {
+        let task = Task {
+            id,
+            status: STATUSES[(id % 4) as usize].into(),
+            group: (id % 5) as u8,
+            score: (id * 7 % 40) as u32,
+        };
+        db.tasks.insert(&task)?;
+        tasks.push(task);
+    }
+    Ok((db, tasks))
+}
+
+/// Sort the ids of the found documents and compare them to the expected documents.
+fn assert_found(
+    found: impl Iterator<Item = Result<Task>>,
+    tasks: &[Task],
+    filter: impl Fn(&Task) -> bool,
+) -> Result<Vec<Task>> {
+    let found = found.collect::<Result<Vec<_>>>()?;
+    let mut found_ids = found.iter().map(|task| task.id).collect::<Vec<_>>();
+    found_ids.sort();
+    let expected = tasks
+        .iter()
+        .filter(|task| filter(task))
+        .map(|task| task.id)
+        .collect::<Vec<_>>();
+    assert!(!expected.is_empty());
+    assert_eq!(found_ids, expected);
+    Ok(found)
+}
+
+#[domacro(all_kv)]
+fn neq_string<K: KV>() -> Result<()> {
+    let (db, tasks) = setup::<K>()?;
+    let found = assert_found(
+        db.tasks
+            .find_many(Task::query().status(ParamTyped::Neq("deleted".into())))?,
+        &tasks,
+        |task| task.status != "deleted",
+    )?;
+    // results are in index key order
+    assert!(found.windows(2).all(|w| w[0].status <= w[1].status));
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn nin_index<K: KV>() -> Result<()> {
+    let (db, tasks) = setup::<K>()?;
+    let found = assert_found(
+        db.tasks
+            .find_many(Task::query().group(ParamTyped::Nin(vec![4, 0, 2, 0])))?,
+        &tasks,
+        |task| task.group == 1 || task.group == 3,
+    )?;
+    assert!(found.windows(2).all(|w| w[0].group <= w[1].group));
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn neq_compound<K: KV>() -> Result<()> {
+    let (db, tasks) = setup::<K>()?;
+    assert_found(
+        db.tasks
+            .find_many(Task::query().group(1).score(ParamTyped::Neq(14)))?,
+        &tasks,
+        |task| task.group == 1 && task.score != 14,
+    )?;
+    assert_found(
+        db.tasks.find_many(
+            Task::query()
+                .group(ParamTyped::In(vec![1, 2]))
+                .score(ParamTyped::Nin(vec![14, 21, 0])),
+        )?,
+        &tasks,
+        |task| [1, 2].contains(&task.group) && ![14, 21, 0].contains(&task.score),
+    )?;
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn nin_empty<K: KV>() -> Result<()> {
+    let (db, tasks) = setup::<K>()?;
+    assert_found(
+        db.tasks
+            .find_many(Task::query().status(ParamTyped::Nin(vec![])))?,
+        &tasks,
+        |_| true,
+    )?;
+    Ok(())
+}
+
+/// Bounds of an index range scanned by the planner.
+type ScanRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Plan a query, returning the table name of the chosen index and the ranges it will scan.
+fn plan<K: KV>(
+    db: &DB<K>,
+    query: <Task as Queryable>::DocumentQuery,
+) -> Result<(String, Vec<ScanRange>)> {
+    let index_fields = db.tasks.extract_index_fields(&query);
+    let index = db.tasks.best_index(&query, &index_fields)?;
+    let ranges = index
+        .scan_ranges(&index_fields)
+        .into_iter()
+        .map(|range| (range.0, range.1))
+        .collect();
+    Ok((index.table_name(), ranges))
+}
+
+/// Concatenate the bytes of a key and a suffix.
+fn key(parts: &[&[u8]]) -> Vec<u8> {
+    parts.concat()
+}
+
+#[test]
+fn should_plan_neq_and_nin() -> Result<()> {
+    let (db, _) = setup::<MemKV>()?;
+
+    let deleted = "deleted".to_string().serialize_lex();
+    let (index, ranges) = plan(&db, Task::query().status(ParamTyped::Neq("deleted".into())))?;
+    assert_eq!(index, "tasks_status");
+    assert_eq!(
+        ranges,
+        vec![
+            (Bound::Unbounded, Bound::Excluded(deleted.clone())),
+            (Bound::Included(key(&[&deleted, &[0x01]])), Bound::Unbounded),
+        ]
+    );
+
+    let (index, ranges) = plan(&db, Task::query().group(ParamTyped::Nin(vec![4, 0, 2, 0])))?;
+    assert_eq!(index, "tasks_group_score");
+    assert_eq!(
+        ranges,
+        vec![
+            (Bound::Unbounded, Bound::Excluded(vec![0])),
+            (Bound::Included(vec![0, 0x01]), Bound::Excluded(vec![2])),
+            (Bound::Included(vec![2, 0x01]), Bound::Excluded(vec![4])),
+            (Bound::Included(vec![4, 0x01]), Bound::Unbounded),
+        ]
+    );
+
+    // an earlier Eq field is kept as the prefix of each range
+    let excluded = key(&[&[1, 0x00], &14u32.serialize_lex()]);
+    let (index, ranges) = plan(&db, Task::query().group(1).score(ParamTyped::Neq(14)))?;
+    assert_eq!(index, "tasks_group_score");
+    assert_eq!(
+        ranges,
+        vec![
+            (Bound::Included(vec![1]), Bound::Excluded(excluded.clone())),
+            (
+                Bound::Included(key(&[&excluded, &[0x01]])),
+                Bound::Included(vec![1, 0x01])
+            ),
+        ]
+    );
+
+    // an unconstrained earlier field spans many values, so the whole index is scanned
+    let query = Task::query().score(ParamTyped::Neq(14));
+    let index_fields = db.tasks.extract_index_fields(&query);
+    let index = db
+        .tasks
+        .indices()
+        .iter()
+        .find(|index| index.table_name() == "tasks_group_score")
+        .unwrap();
+    assert_eq!(
+        index
+            .scan_ranges(&index_fields)
+            .into_iter()
+            .map(|range| (range.0, range.1))
+            .collect::<Vec<_>>(),
+        vec![(
+            Bound::Included(vec![0]),
+            Bound::Included(vec![u8::MAX, 0x01])
+        )]
+    );
+    Ok(())
+}
@@ -29,6 +29,61 @@ fn compound_primary_key() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn get_by_primary_key() -> Result<()> {
+    #[derive(AnonDB)]
+    pub struct DB<K: KV> {
+        #[anondb(primary_key = id0)]
+        #[anondb(index = id1)]
+        pub test: Collection<TestDocument, K, u128>,
+    }
+    let db = DB::<MemKV>::in_memory(None)?;
+
+    let docs = (0..10).map(|_| TestDocument::default()).collect::<Vec<_>>();
+    for doc in &docs {
+        db.test.insert(doc)?;
+    }
+    for doc in &docs {
+        assert_eq!(db.test.get(&doc.id0)?.as_ref(), Some(doc));
+    }
+    assert_eq!(db.test.get(&rand::random::<u128>())?, None);
+
+    let missing = rand::random::<u128>();
+    let keys = [docs[3].id0, missing, docs[1].id0];
+    assert_eq!(
+        db.test.get_many(&keys)?,
+        vec![Some(docs[3].clone()), None, Some(docs[1].clone())]
+    );
+    Ok(())
+}
+
+#[test]
+fn get_by_compound_primary_key() -> Result<()> {
+    #[derive(AnonDB)]
+    pub struct DB<K: KV> {
+        #[anondb(primary_key = id0, str)]
+        pub test: Collection<TestDocument, K, (u128, String)>,
+    }
+    let db = DB::<RedbKV>::in_memory(None)?;
+
+    let doc0 = TestDocument::default();
+    let mut doc1 = doc0.clone();
+    doc1.str = format!("{}a", doc0.str);
+    db.test.insert(&doc0)?;
+    db.test.insert(&doc1)?;
+
+    assert_eq!(
+        db.test.get(&(doc0.id0, doc0.str.clone()))?,
+        Some(doc0.clone())
+    );
+    assert_eq!(
+        db.test.get(&(doc1.id0, doc1.str.clone()))?,
+        Some(doc1.clone())
+    );
+    assert_eq!(db.test.get(&(doc0.id0, String::default()))?, None);
+    Ok(())
+}
+
 // #[test]
 // fn fail_to_start_multiple_primary_keys() -> Result<()> {
 //     #[derive(Debug, AnonDB)]
@@ -18,7 +18,7 @@ fn should_insert_document() -> Result<()> {
     let doc = db
         .test
         .find_many(TestDocument::query().id3(99..u128::MAX))?
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>>>()?;
     println!("found {}", doc.len());
 
     Ok(())
@@ -0,0 +1,64 @@
+use super::*;
+
+#[domacro(all_kv)]
+fn rebuild<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..20 {
+        db.accounts.insert(&account(id))?;
+    }
+    // lose some index entries
+    db.kv().clear("accounts_name_unique")?;
+    db.kv()
+        .remove_all_multimap("accounts_group", 1u8.serialize_lex().as_slice())?;
+    assert_eq!(db.accounts.find_many(Account::query().group(1))?.count(), 0);
+
+    db.accounts.rebuild_indices()?;
+    assert_eq!(db.kv().count("accounts_name_unique")?, 20);
+    // non-unique indices are rebuilt as multimaps
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 20);
+    let ids = db
+        .accounts
+        .find_many(Account::query().group(1))?
+        .map(|account| account.map(|account| account.id))
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(ids, vec![1, 5, 9, 13, 17]);
+    assert_eq!(
+        db.accounts
+            .find_one(Account::query().name("account-7"))?
+            .map(|account| account.id),
+        Some(7)
+    );
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn rebuild_unique_violation<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..5 {
+        db.accounts.insert(&account(id))?;
+    }
+    // write a document that bypasses the unique index
+    let duplicate = Account {
+        name: "account-1".into(),
+        ..account(100)
+    };
+    db.kv().insert(
+        "accounts",
+        100u64.serialize_lex().as_slice(),
+        &rmp_serde::to_vec_named(&duplicate)?,
+    )?;
+
+    db.accounts
+        .rebuild_indices()
+        .expect_err("should fail to rebuild an index with a unique constraint violation");
+    // the existing indices are untouched
+    assert_eq!(db.kv().count("accounts_name_unique")?, 5);
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 5);
+    assert_eq!(
+        db.accounts
+            .find_one(Account::query().name("account-1"))?
+            .map(|account| account.id),
+        Some(1)
+    );
+    Ok(())
+}
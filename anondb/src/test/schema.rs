@@ -0,0 +1,314 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct AccountWithNote {
+    pub id: u64,
+    pub name: String,
+    pub group: u8,
+    #[serde(default)]
+    pub note: String,
+    #[serde(rename = "alias")]
+    pub nickname: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct AccountWithBalance {
+    pub id: u64,
+    pub name: String,
+    pub group: u8,
+    pub balance: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct AccountWideGroup {
+    pub id: u64,
+    pub name: String,
+    pub group: u16,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Profile {
+    pub id: u64,
+    pub display_name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Document)]
+#[serde(rename_all = "camelCase")]
+pub struct CamelProfile {
+    pub id: u64,
+    pub display_name: String,
+    #[serde(rename = "avatar")]
+    pub avatar_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen_at: Option<u64>,
+    #[serde(skip)]
+    pub is_cached: bool,
+    pub r#type: u8,
+}
+
+#[derive(AnonDB)]
+pub struct ProfileDB<K: KV> {
+    #[anondb(primary_key = id)]
+    pub profiles: Collection<Profile, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct CamelProfileDB<K: KV> {
+    #[anondb(primary_key = id)]
+    pub profiles: Collection<CamelProfile, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group)]
+    pub accounts: Collection<Account, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct RemovedIndexDB<K: KV> {
+    #[anondb(primary_key = id)]
+    pub accounts: Collection<Account, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct UniqueIndexDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group; unique = true)]
+    pub accounts: Collection<Account, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct ChangedPrimaryKeyDB<K: KV> {
+    #[anondb(primary_key = name)]
+    #[anondb(index = group)]
+    pub accounts: Collection<Account, K, String>,
+}
+
+#[derive(AnonDB)]
+pub struct NoteDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group)]
+    pub accounts: Collection<AccountWithNote, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct BalanceDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group)]
+    pub accounts: Collection<AccountWithBalance, K, u64>,
+}
+
+#[derive(AnonDB)]
+pub struct WideGroupDB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = group)]
+    pub accounts: Collection<AccountWideGroup, K, u64>,
+}
+
+#[domacro(all_kv)]
+fn store_metadata<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    let metadata = MetadataDocument::load(&db.kv().read_tx()?)?
+        .expect("metadata should be stored on first start");
+    assert_eq!(metadata.version, METADATA_VERSION);
+    assert_eq!(
+        metadata.collections["accounts"].primary_key,
+        vec!["id".to_string()]
+    );
+    assert_eq!(
+        metadata.indices_by_collection["accounts"]
+            .iter()
+            .map(|index| index.table_name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["accounts_group"]
+    );
+
+    // reopening with the same schema succeeds
+    let db = DB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    // adding an index is compatible, and is stored
+    let db = AccountDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    let metadata = MetadataDocument::load(&db.kv().read_tx()?)?.unwrap();
+    assert_eq!(metadata.indices_by_collection["accounts"].len(), 2);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn refuse_incompatible<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    db.accounts.insert(&account(0))?;
+    let bytes = db.export_bytes()?;
+
+    let err = ChangedPrimaryKeyDB::<K>::in_memory(Some(&bytes))
+        .err()
+        .expect("should refuse to start with a changed primary key");
+    assert!(
+        err.to_string()
+            .contains("collection \"accounts\" primary key changed from (id) to (name)")
+    );
+    // the stored schema is unchanged
+    let db = DB::<K>::in_memory(Some(&bytes))?;
+    assert_eq!(db.accounts.count()?, 1);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn apply_index_changes<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for id in 0..4 {
+        db.accounts.insert(&account(id))?;
+    }
+
+    // removed indices have their tables dropped
+    let removed = RemovedIndexDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    assert_eq!(removed.kv().count_multimap("accounts_group")?, 0);
+    assert!(IndexBuildState::load(&removed.kv().read_tx()?, "accounts_group")?.is_none());
+    // and are built again when added back
+    let readded = DB::<K>::in_memory(Some(&removed.export_bytes()?))?;
+    readded.build_indices(10)?;
+    assert_eq!(readded.kv().count_multimap("accounts_group")?, 4);
+
+    // changed indices are rebuilt when opened
+    let unique = UniqueIndexDB::<K>::in_memory(Some(&db.export_bytes()?))?;
+    assert!(unique.index_build_progress()?.is_empty());
+    assert_eq!(unique.kv().count_multimap("accounts_group")?, 0);
+    assert_eq!(unique.kv().count("accounts_group_unique")?, 4);
+    assert_eq!(
+        unique
+            .accounts
+            .find_one(Account::query().group(2))?
+            .map(|account| account.id),
+        Some(2)
+    );
+    let metadata = MetadataDocument::load(&unique.kv().read_tx()?)?.unwrap();
+    assert!(metadata.indices_by_collection["accounts"][0].options.unique);
+
+    // a changed index that violates a unique constraint refuses to open, and nothing is changed
+    db.accounts.insert(&account(4))?;
+    let bytes = db.export_bytes()?;
+    let err = UniqueIndexDB::<K>::in_memory(Some(&bytes))
+        .err()
+        .expect("should refuse to start with a unique constraint violation");
+    assert!(err.to_string().contains("uniqueness constraint violated"));
+    let db = DB::<K>::in_memory(Some(&bytes))?;
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 5);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn compare_fields<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    db.accounts.insert(&account(0))?;
+    let bytes = db.export_bytes()?;
+
+    // fields that may be missing from stored documents are compatible
+    let db = NoteDB::<K>::in_memory(Some(&bytes))?;
+    let account = db.accounts.get(&0u64)?.unwrap();
+    assert_eq!(account.note, "");
+    assert_eq!(account.nickname, None);
+    let metadata = MetadataDocument::load(&db.kv().read_tx()?)?.unwrap();
+    assert!(
+        metadata.collections["accounts"]
+            .fields
+            .contains_key("alias")
+    );
+
+    let err = BalanceDB::<K>::in_memory(Some(&bytes))
+        .err()
+        .expect("should refuse to start with a required field added");
+    assert!(
+        err.to_string()
+            .contains("collection \"accounts\" field \"balance\" added with type u64")
+    );
+    let err = WideGroupDB::<K>::in_memory(Some(&bytes))
+        .err()
+        .expect("should refuse to start with a changed field type");
+    assert!(
+        err.to_string()
+            .contains("collection \"accounts\" field \"group\" changed from type u8 to u16")
+    );
+    Ok(())
+}
+
+#[test]
+fn should_describe_renamed_document_fields() {
+    let fields = CamelProfile::FIELDS
+        .iter()
+        .map(|field| (field.name, field.serialized_name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        vec![
+            ("id", "id"),
+            ("display_name", "displayName"),
+            ("avatar_url", "avatar"),
+            ("last_seen_at", "lastSeenAt"),
+            ("type", "type"),
+        ]
+    );
+}
+
+#[domacro(all_kv)]
+fn detect_rename_all<K: KV>() -> Result<()> {
+    let db = ProfileDB::<K>::in_memory(None)?;
+    db.profiles.insert(&Profile {
+        id: 0,
+        display_name: "zero".into(),
+    })?;
+    let err = CamelProfileDB::<K>::in_memory(Some(&db.export_bytes()?))
+        .err()
+        .expect("should refuse to start with renamed fields");
+    assert!(
+        err.to_string()
+            .contains("collection \"profiles\" field \"displayName\" added with type String")
+    );
+    assert!(
+        err.to_string()
+            .contains("collection \"profiles\" field \"display_name\" removed")
+    );
+    Ok(())
+}
+
+#[test]
+fn should_describe_document_fields() {
+    assert_eq!(
+        AccountWithNote::FIELDS,
+        &[
+            FieldSchema {
+                name: "id",
+                serialized_name: "id",
+                ty: "u64",
+                optional: false,
+                default: false,
+            },
+            FieldSchema {
+                name: "name",
+                serialized_name: "name",
+                ty: "String",
+                optional: false,
+                default: false,
+            },
+            FieldSchema {
+                name: "group",
+                serialized_name: "group",
+                ty: "u8",
+                optional: false,
+                default: false,
+            },
+            FieldSchema {
+                name: "note",
+                serialized_name: "note",
+                ty: "String",
+                optional: false,
+                default: true,
+            },
+            FieldSchema {
+                name: "nickname",
+                serialized_name: "alias",
+                ty: "Option<String>",
+                optional: true,
+                default: false,
+            },
+        ]
+    );
+}
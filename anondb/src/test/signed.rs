@@ -0,0 +1,48 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Event {
+    pub id: u64,
+    pub timestamp: i64,
+    pub offset: i32,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = timestamp)]
+    #[anondb(index = offset, timestamp; unique = true)]
+    pub events: Collection<Event, K>,
+}
+
+#[domacro(all_kv)]
+fn query_signed<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for id in 0..20u64 {
+        let timestamp = id as i64 * 10 - 100;
+        db.events.insert(&Event {
+            id,
+            timestamp,
+            offset: (id % 3) as i32 - 1,
+        })?;
+    }
+
+    // results may be returned in the order of the index used
+    let ids = |query| -> Result<Vec<u64>> {
+        let mut ids = db
+            .events
+            .find_many(query)?
+            .map(|event| event.map(|event| event.id))
+            .collect::<Result<Vec<_>>>()?;
+        ids.sort();
+        Ok(ids)
+    };
+    assert_eq!(ids(Event::query().timestamp(-30..10))?, vec![7, 8, 9, 10]);
+    assert_eq!(ids(Event::query().timestamp(..=-90))?, vec![0, 1]);
+    assert_eq!(ids(Event::query().timestamp(-100))?, vec![0]);
+    assert_eq!(
+        ids(Event::query().offset(-1).timestamp(-50..))?,
+        vec![6, 9, 12, 15, 18]
+    );
+    Ok(())
+}
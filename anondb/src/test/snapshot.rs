@@ -0,0 +1,80 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Order {
+    pub id: u64,
+    pub customer: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Customer {
+    pub id: u8,
+    pub order_count: u64,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = customer)]
+    pub orders: Collection<Order, K, u64>,
+    #[anondb(primary_key = id)]
+    pub customers: Collection<Customer, K, u8>,
+}
+
+fn place_order<K: KV>(db: &DB<K>, id: u64, customer: u8) -> Result<()> {
+    db.transaction(|tx| {
+        tx.orders.insert(&Order { id, customer })?;
+        let mut customer = tx.customers.get(&customer)?.unwrap_or(Customer {
+            id: customer,
+            order_count: 0,
+        });
+        customer.order_count += 1;
+        tx.customers.upsert(&customer)?;
+        Ok(())
+    })
+}
+
+#[domacro(all_kv)]
+fn snapshot<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        place_order(&db, id, 0)?;
+    }
+
+    let snapshot = db.snapshot()?;
+    let orders = snapshot.orders.find_many(Order::query().customer(0))?;
+    for id in 10..20 {
+        place_order(&db, id, 0)?;
+    }
+
+    // every read through the snapshot sees the state when it was opened
+    assert_eq!(orders.count(), 10);
+    assert_eq!(snapshot.orders.count()?, 10);
+    assert_eq!(
+        snapshot
+            .orders
+            .find_many(Order::query().customer(0))?
+            .count(),
+        10
+    );
+    assert_eq!(snapshot.orders.get(&15u64)?, None);
+    assert_eq!(
+        snapshot.customers.get(&0u8)?.map(|c| c.order_count),
+        Some(10)
+    );
+    assert_eq!(
+        snapshot.orders.get_many(&[5u64, 15])?,
+        vec![Some(Order { id: 5, customer: 0 }), None]
+    );
+
+    // a new snapshot sees the latest state
+    let (orders, order_count) = db.read(|snapshot| {
+        Ok((
+            snapshot.orders.count()?,
+            snapshot.customers.get(&0u8)?.map(|c| c.order_count),
+        ))
+    })?;
+    assert_eq!(orders, 20);
+    assert_eq!(order_count, Some(20));
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use super::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct User {
+    pub id: u64,
+    pub name: String,
+    pub post_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Document)]
+pub struct Post {
+    pub id: u64,
+    pub author_id: u64,
+}
+
+#[derive(AnonDB)]
+pub struct DB<K: KV> {
+    #[anondb(primary_key = id)]
+    #[anondb(index = name; unique = true)]
+    pub users: Collection<User, K, u64>,
+    #[anondb(primary_key = id)]
+    #[anondb(index = author_id)]
+    pub posts: Collection<Post, K, u64>,
+}
+
+/// Insert a post and increment the author's post count.
+fn create_post(tx: &DBTx<'_, impl KV>, post: &Post) -> Result<()> {
+    tx.posts.insert(post)?;
+    let Some(mut user) = tx.users.get(&post.author_id)? else {
+        anyhow::bail!("author does not exist");
+    };
+    user.post_count += 1;
+    tx.users.replace(&user)?;
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn transaction<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    let user = User {
+        id: 0,
+        name: "alice".into(),
+        post_count: 0,
+    };
+
+    let count = db.transaction(|tx| {
+        tx.users.insert(&user)?;
+        // writes are visible within the transaction
+        assert_eq!(tx.users.get(&0u64)?, Some(user.clone()));
+        for id in 0..3 {
+            create_post(tx, &Post { id, author_id: 0 })?;
+        }
+        // but not outside of it until commit
+        assert_eq!(db.users.get(&0u64)?, None);
+        Ok(tx.users.get(&0u64)?.map(|user| user.post_count))
+    })?;
+    assert_eq!(count, Some(3));
+    assert_eq!(db.users.get(&0u64)?.map(|user| user.post_count), Some(3));
+    assert_eq!(db.posts.find_many(Post::query().author_id(0))?.count(), 3);
+
+    // an error aborts writes to every collection
+    db.transaction(|tx| {
+        create_post(
+            tx,
+            &Post {
+                id: 10,
+                author_id: 1,
+            },
+        )
+    })
+    .expect_err("should fail to create a post without an author");
+    db.transaction(|tx| {
+        tx.posts.delete(&0u64)?;
+        create_post(
+            tx,
+            &Post {
+                id: 1,
+                author_id: 0,
+            },
+        )
+    })
+    .expect_err("should fail to create a duplicate post");
+    assert_eq!(db.posts.count()?, 3);
+    assert_eq!(db.posts.get(&10u64)?, None);
+    assert_eq!(db.users.get(&0u64)?.map(|user| user.post_count), Some(3));
+    Ok(())
+}
+
+/// Create a user if the name is not taken, checked inside the transaction.
+fn register(tx: &DBTx<'_, impl KV>, id: u64, name: &str) -> Result<bool> {
+    if tx.users.find_one(User::query().name(name))?.is_some() {
+        return Ok(false);
+    }
+    tx.users.insert(&User {
+        id,
+        name: name.into(),
+        post_count: 0,
+    })?;
+    Ok(true)
+}
+
+#[domacro(all_kv)]
+fn query_pending_writes<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    db.transaction(|tx| {
+        assert!(register(tx, 0, "alice")?);
+        assert!(!register(tx, 1, "alice")?);
+        assert!(register(tx, 1, "bob")?);
+        for id in 0..6 {
+            tx.posts.insert(&Post {
+                id,
+                author_id: id % 2,
+            })?;
+        }
+        // pending inserts
+        let posts = tx.posts.find_many(Post::query().author_id(1))?;
+        assert_eq!(
+            posts.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![1, 3, 5]
+        );
+        let posts = tx
+            .posts
+            .find_many(Post::query().author_id(ParamTyped::In(vec![0, 1])).id(..3))?;
+        // results are in index order
+        assert_eq!(
+            posts.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![0, 2, 1]
+        );
+
+        // pending updates
+        tx.posts
+            .update(Post::query().id(3), |post| post.author_id = 0)?;
+        let posts = tx.posts.find_many(Post::query().author_id(0))?;
+        assert_eq!(
+            posts.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![0, 2, 3, 4]
+        );
+
+        // pending deletes
+        assert_eq!(tx.posts.delete_many(Post::query().author_id(0))?, 4);
+        assert_eq!(tx.posts.find_many(Post::query())?.len(), 2);
+        assert_eq!(
+            tx.posts
+                .delete_one(Post::query().author_id(1))?
+                .map(|p| p.id),
+            Some(1)
+        );
+        assert_eq!(tx.posts.find_one(Post::query())?.map(|p| p.id), Some(5));
+        Ok(())
+    })?;
+    assert_eq!(db.users.count()?, 2);
+    assert_eq!(db.posts.count()?, 1);
+    assert!(!db.transaction(|tx| register(tx, 2, "bob"))?);
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn handle_constraint_errors<K: KV>() -> Result<()> {
+    let db = DB::<K>::in_memory(None)?;
+    let user = |id, name: &str| User {
+        id,
+        name: name.into(),
+        post_count: 0,
+    };
+    db.users.insert(&user(0, "alice"))?;
+    db.users.insert(&user(1, "bob"))?;
+
+    db.transaction(|tx| {
+        // failed writes are caught and the transaction continues without partial writes
+        assert!(tx.users.insert(&user(2, "alice")).is_err());
+        assert_eq!(tx.users.get(&2u64)?, None);
+        assert!(tx.users.replace(&user(1, "alice")).is_err());
+        assert!(tx.users.upsert(&user(3, "bob")).is_err());
+        assert_eq!(tx.users.get(&3u64)?, None);
+        assert!(
+            tx.users
+                .update(User::query(), |user| user.name = "carol".into())
+                .is_err()
+        );
+        assert_eq!(tx.users.find_one(User::query().name("carol"))?, None);
+        // documents may swap unique values with each other
+        tx.users.update(User::query(), |user| {
+            user.name = if user.name == "alice" { "bob" } else { "alice" }.into()
+        })?;
+        tx.users.insert(&user(2, "carol"))?;
+        Ok(())
+    })?;
+
+    assert_eq!(db.users.count()?, 3);
+    assert_eq!(db.kv().count("users_name_unique")?, 3);
+    assert_eq!(db.users.get(&0u64)?, Some(user(0, "bob")));
+    assert_eq!(db.users.get(&1u64)?, Some(user(1, "alice")));
+    assert_eq!(db.users.get(&3u64)?, None);
+    assert_eq!(
+        db.users
+            .find_one(User::query().name("alice"))?
+            .map(|user| user.id),
+        Some(1)
+    );
+    Ok(())
+}
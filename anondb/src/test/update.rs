@@ -0,0 +1,151 @@
+use super::*;
+
+fn ids(db: &AccountDB<impl KV>, query: Account_Query) -> Result<Vec<u64>> {
+    db.accounts
+        .find_many(query)?
+        .map(|account| account.map(|account| account.id))
+        .collect()
+}
+
+#[domacro(all_kv)]
+fn replace<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..4 {
+        db.accounts.insert(&account(id))?;
+    }
+
+    let doc = Account {
+        name: "renamed".into(),
+        group: 2,
+        ..account(1)
+    };
+    db.accounts.replace(&doc)?;
+    assert_eq!(db.accounts.get(&1u64)?, Some(doc.clone()));
+    assert_eq!(db.accounts.count()?, 4);
+    assert!(ids(&db, Account::query().group(1))?.is_empty());
+    assert_eq!(ids(&db, Account::query().group(2))?, vec![1, 2]);
+    assert_eq!(ids(&db, Account::query().name("renamed"))?, vec![1]);
+    assert!(ids(&db, Account::query().name("account-1"))?.is_empty());
+
+    // the old unique value is released
+    db.accounts.insert(&Account {
+        name: "account-1".into(),
+        ..account(10)
+    })?;
+
+    // unique constraints are checked against other documents
+    db.accounts
+        .replace(&Account {
+            name: "account-2".into(),
+            ..account(3)
+        })
+        .expect_err("should fail to replace with a duplicate unique value");
+    assert_eq!(db.accounts.get(&3u64)?, Some(account(3)));
+
+    db.accounts
+        .replace(&account(100))
+        .expect_err("should fail to replace a document that does not exist");
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn update<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..10 {
+        db.accounts.insert(&account(id))?;
+    }
+
+    let updated = db
+        .accounts
+        .update(Account::query().group(1), |account| account.group = 4)?;
+    assert_eq!(updated, 3);
+    assert!(ids(&db, Account::query().group(1))?.is_empty());
+    assert_eq!(ids(&db, Account::query().group(4))?, vec![1, 5, 9]);
+    assert_eq!(ids(&db, Account::query().group(0))?, vec![0, 4, 8]);
+    assert_eq!(
+        db.accounts
+            .update(Account::query().group(1), |account| account.group = 5)?,
+        0
+    );
+
+    // documents being updated may swap unique values
+    db.accounts.update(Account::query().id(0..2), |account| {
+        account.name = format!("account-{}", 1 - account.id)
+    })?;
+    assert_eq!(ids(&db, Account::query().name("account-0"))?, vec![1]);
+    assert_eq!(ids(&db, Account::query().name("account-1"))?, vec![0]);
+
+    // a failed update leaves every document unchanged
+    db.accounts
+        .update(Account::query().group(0), |account| {
+            account.name = "same".into()
+        })
+        .expect_err("should fail to update to a duplicate unique value");
+    assert!(ids(&db, Account::query().name("same"))?.is_empty());
+    assert_eq!(db.accounts.get(&4u64)?, Some(account(4)));
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn update_primary_key<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    for id in 0..3 {
+        db.accounts.insert(&account(id))?;
+    }
+
+    let err = db
+        .accounts
+        .update(Account::query().group(0..2), |account| {
+            account.group = 3;
+            if account.id == 1 {
+                account.id = 100;
+            }
+        })
+        .expect_err("should fail to change the primary key of a document");
+    assert!(
+        err.to_string()
+            .contains("Collection \"accounts\" update cannot change the primary key of a document")
+    );
+    // nothing is written
+    assert_eq!(db.accounts.get(&0u64)?, Some(account(0)));
+    assert_eq!(db.accounts.get(&1u64)?, Some(account(1)));
+    assert_eq!(db.accounts.get(&100u64)?, None);
+    assert!(ids(&db, Account::query().group(3))?.is_empty());
+    Ok(())
+}
+
+#[domacro(all_kv)]
+fn upsert<K: KV>() -> Result<()> {
+    let db = AccountDB::<K>::in_memory(None)?;
+    assert_eq!(db.accounts.upsert(&account(0))?, None);
+    assert_eq!(db.accounts.upsert(&account(1))?, None);
+    // upserting an identical document keeps its own unique values
+    assert_eq!(db.accounts.upsert(&account(0))?, Some(account(0)));
+    assert_eq!(db.accounts.count()?, 2);
+
+    let moved = Account {
+        group: 3,
+        ..account(0)
+    };
+    assert_eq!(db.accounts.upsert(&moved)?, Some(account(0)));
+    assert!(ids(&db, Account::query().group(0))?.is_empty());
+    assert_eq!(ids(&db, Account::query().group(3))?, vec![0]);
+    assert_eq!(db.kv().count("accounts_name_unique")?, 2);
+    assert_eq!(db.kv().count_multimap("accounts_group")?, 2);
+
+    db.accounts
+        .upsert(&Account {
+            name: "account-1".into(),
+            ..moved.clone()
+        })
+        .expect_err("should fail to upsert a unique value used by another document");
+    db.accounts
+        .upsert(&Account {
+            name: "account-0".into(),
+            ..account(2)
+        })
+        .expect_err("should fail to insert a unique value used by another document");
+    assert_eq!(db.accounts.get(&0u64)?, Some(moved));
+    assert_eq!(db.accounts.get(&2u64)?, None);
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::*;
+use anondb_kv::*;
+
+/// A handle to a collection that performs every operation in a shared write transaction. Writes
+/// are visible to later operations through the same transaction, and are committed or discarded
+/// together. Created by the `transaction` function generated by the AnonDB derive macro.
+pub struct CollectionTx<'tx, T, K: KV, PK = UndeclaredKey>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    collection: &'tx Collection<T, K, PK>,
+    tx: &'tx K::WriteTransaction,
+}
+
+impl<'tx, T, K: KV, PK> CollectionTx<'tx, T, K, PK>
+where
+    T: 'static + Serialize + for<'de> Deserialize<'de> + Queryable,
+{
+    pub fn new(collection: &'tx Collection<T, K, PK>, tx: &'tx K::WriteTransaction) -> Self {
+        Self { collection, tx }
+    }
+
+    /// The collection this handle operates on.
+    pub fn collection(&self) -> &'tx Collection<T, K, PK> {
+        self.collection
+    }
+
+    /// Retrieve a document by primary key. See `Collection::get`.
+    pub fn get(&self, primary_key: &PK) -> Result<Option<T>> {
+        let key = self.collection.serialize_primary_key(primary_key);
+        self.get_document(&key)
+    }
+
+    /// Retrieve many documents by primary key. See `Collection::get_many`.
+    pub fn get_many<'a>(
+        &self,
+        primary_keys: impl IntoIterator<Item = &'a PK>,
+    ) -> Result<Vec<Option<T>>>
+    where
+        PK: 'a,
+    {
+        primary_keys
+            .into_iter()
+            .map(|primary_key| self.get(primary_key))
+            .collect()
+    }
+
+    /// Insert a document. See `Collection::insert`.
+    pub fn insert(&self, document: &T) -> Result<()> {
+        self.check_unique([document], &[])?;
+        self.insert_document(document)
+    }
+
+    /// Write a document and all index entries.
+    fn insert_document(&self, document: &T) -> Result<()> {
+        let primary_key = (self.collection.primary_key_extractor())(document);
+        self.collection
+            .primary_key_index()
+            .insert(self.tx, document, &primary_key)?;
+        for index in self.collection.indices() {
+            index.insert(self.tx, document, &primary_key)?;
+        }
+        Ok(())
+    }
+
+    /// Insert many documents. See `Collection::insert_many`.
+    pub fn insert_many<'a>(
+        &self,
+        documents: impl IntoIterator<Item = &'a T>,
+        mode: InsertMode,
+    ) -> Result<InsertReport> {
+        let mut report = InsertReport::default();
+        for (position, document) in documents.into_iter().enumerate() {
+            if mode == InsertMode::SkipDuplicates {
+                let mut violated = None;
+                for index in std::iter::once(self.collection.primary_key_index())
+                    .chain(self.collection.indices())
+                {
+                    if index.is_violated_by(self.tx, document)? {
+                        violated = Some(index.table_name());
+                        break;
+                    }
+                }
+                if let Some(index) = violated {
+                    report.skipped.push(SkippedDocument { position, index });
+                    continue;
+                }
+            }
+            self.insert(document)?;
+            report.inserted += 1;
+        }
+        Ok(report)
+    }
+
+    /// Replace the stored document with the same primary key. See `Collection::replace`.
+    pub fn replace(&self, document: &T) -> Result<()> {
+        let primary_key = (self.collection.primary_key_extractor())(document);
+        let Some(old_document) = self.get_document(&primary_key)? else {
+            anyhow::bail!(
+                "Collection \"{}\" cannot replace document, no document exists with the primary key",
+                self.collection.name()
+            );
+        };
+        self.check_unique([document], &[self.unique_keys(&old_document)])?;
+        self.replace_document(&old_document, document)
+    }
+
+    /// Insert or overwrite a document. See `Collection::upsert`.
+    pub fn upsert(&self, document: &T) -> Result<Option<T>> {
+        let primary_key = (self.collection.primary_key_extractor())(document);
+        let old_document = self.get_document(&primary_key)?;
+        match &old_document {
+            Some(old_document) => {
+                self.check_unique([document], &[self.unique_keys(old_document)])?;
+                self.replace_document(old_document, document)?;
+            }
+            None => self.insert(document)?,
+        }
+        Ok(old_document)
+    }
+
+    /// Delete the document with a primary key. See `Collection::delete`.
+    pub fn delete(&self, primary_key: &PK) -> Result<Option<T>> {
+        let key = self.collection.serialize_primary_key(primary_key);
+        let Some(document) = self.get_document(&key)? else {
+            return Ok(None);
+        };
+        self.remove_document(&document)?;
+        Ok(Some(document))
+    }
+
+    /// Load every document matching a query. Pending writes in the transaction are visible. See
+    /// `Index::query_buffered`.
+    pub fn find_many(&self, query: T::DocumentQuery) -> Result<Vec<T>> {
+        self.find(query, None)
+    }
+
+    /// Load the first document matching a query. Pending writes in the transaction are visible.
+    pub fn find_one(&self, query: T::DocumentQuery) -> Result<Option<T>> {
+        Ok(self.find(query, Some(1))?.pop())
+    }
+
+    /// Apply `update` to every document matching a query. See `Collection::update`.
+    pub fn update(&self, query: T::DocumentQuery, mut update: impl FnMut(&mut T)) -> Result<u64> {
+        let mut documents = self.find_many(query)?;
+        let primary_key_extractor = self.collection.primary_key_extractor();
+        let mut primary_keys = Vec::with_capacity(documents.len());
+        let mut removed = Vec::with_capacity(documents.len());
+        for document in &mut documents {
+            primary_keys.push(primary_key_extractor(document));
+            removed.push(self.unique_keys(document));
+            update(document);
+            if primary_key_extractor(document) != *primary_keys.last().unwrap() {
+                anyhow::bail!(
+                    "Collection \"{}\" update cannot change the primary key of a document",
+                    self.collection.name()
+                );
+            }
+        }
+        // documents may swap unique values with each other, so the constraints are checked as if
+        // every matched document was removed first
+        self.check_unique(&documents, &removed)?;
+        for primary_key in &primary_keys {
+            if let Some(old_document) = self.get_document(primary_key)? {
+                self.remove_document(&old_document)?;
+            }
+        }
+        for document in &documents {
+            self.insert_document(document)?;
+        }
+        Ok(documents.len() as u64)
+    }
+
+    /// Delete the first document matching a query. See `Collection::delete_one`.
+    pub fn delete_one(&self, query: T::DocumentQuery) -> Result<Option<T>> {
+        let Some(document) = self.find_one(query)? else {
+            return Ok(None);
+        };
+        self.remove_document(&document)?;
+        Ok(Some(document))
+    }
+
+    /// Delete every document matching a query. See `Collection::delete_many`.
+    pub fn delete_many(&self, query: T::DocumentQuery) -> Result<u64> {
+        let documents = self.find_many(query)?;
+        for document in &documents {
+            self.remove_document(document)?;
+        }
+        Ok(documents.len() as u64)
+    }
+
+    fn find(&self, query: T::DocumentQuery, limit: Option<usize>) -> Result<Vec<T>> {
+        let index_fields = self.collection.extract_index_fields(&query);
+        let best_index = self.collection.best_index(&query, &index_fields)?;
+        best_index.query_buffered(self.tx, &query, &index_fields, limit)
+    }
+
+    /// Clear a secondary index and insert an entry for every document in the collection. Unique
+    /// constraints are checked as entries are inserted.
+    pub(crate) fn rebuild_index(&self, index: &Index<T>) -> Result<()> {
+        if index.options.primary {
+            anyhow::bail!(
+                "Collection \"{}\" cannot rebuild the primary index",
+                self.collection.name()
+            );
+        }
+        let table_name = index.table_name();
+        if index.options.unique {
+            self.tx.clear(&table_name)?;
+        } else {
+            self.tx.clear_multimap(&table_name)?;
+        }
+        // write transactions may be written to while iterating, the index table is not part of
+        // the range
+        for item in self.tx.range(self.collection.name(), ..)? {
+            let item = item?;
+            let document = rmp_serde::from_slice::<T>(item.value())?;
+            index.insert(self.tx, &document, item.key())?;
+        }
+        IndexBuildState::complete().store(self.tx, &table_name)?;
+        Ok(())
+    }
+
+    /// Serialized keys of a document in the primary index and every unique index, in the order of
+    /// `unique_indices`.
+    fn unique_keys(&self, document: &T) -> Vec<Vec<u8>> {
+        self.unique_indices()
+            .map(|index| (index.serialize)(document))
+            .collect()
+    }
+
+    fn unique_indices(&self) -> impl Iterator<Item = &Arc<Index<T>>> {
+        std::iter::once(self.collection.primary_key_index())
+            .chain(self.collection.indices())
+            .filter(|index| index.options.unique)
+    }
+
+    /// Fail if writing `documents` would violate the primary key or a unique index, once the
+    /// documents with the `removed` keys (from `unique_keys`) are deleted. This is checked before
+    /// anything is written, so a failed operation leaves the transaction unchanged and the error
+    /// may be handled without discarding the transaction.
+    fn check_unique<'a>(
+        &self,
+        documents: impl IntoIterator<Item = &'a T> + Clone,
+        removed: &[Vec<Vec<u8>>],
+    ) -> Result<()> {
+        for (i, index) in self.unique_indices().enumerate() {
+            let table_name = index.table_name();
+            let removed_keys = removed
+                .iter()
+                .map(|keys| keys[i].as_slice())
+                .collect::<HashSet<_>>();
+            let mut keys = HashSet::<Vec<u8>>::default();
+            for document in documents.clone() {
+                let key = (index.serialize)(document);
+                let stored = !removed_keys.contains(key.as_slice())
+                    && self.tx.get(&table_name, &key)?.is_some();
+                if stored || !keys.insert(key) {
+                    anyhow::bail!(
+                        "Collection \"{}\" index \"{}\" cannot write document, uniqueness constraint violated",
+                        self.collection.name(),
+                        table_name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Overwrite a stored document with a document that has the same primary key. Only changed
+    /// index entries are written.
+    fn replace_document(&self, old_document: &T, document: &T) -> Result<()> {
+        let primary_key = (self.collection.primary_key_extractor())(document);
+        self.collection.primary_key_index().replace(
+            self.tx,
+            old_document,
+            document,
+            &primary_key,
+        )?;
+        for index in self.collection.indices() {
+            index.replace(self.tx, old_document, document, &primary_key)?;
+        }
+        Ok(())
+    }
+
+    /// Remove a stored document and all index entries.
+    fn remove_document(&self, document: &T) -> Result<()> {
+        let primary_key = (self.collection.primary_key_extractor())(document);
+        self.collection
+            .primary_key_index()
+            .remove(self.tx, document, &primary_key)?;
+        for index in self.collection.indices() {
+            index.remove(self.tx, document, &primary_key)?;
+        }
+        Ok(())
+    }
+
+    /// Load a document by serialized primary key.
+    fn get_document(&self, primary_key: &[u8]) -> Result<Option<T>> {
+        match self.tx.get(self.collection.name(), primary_key)? {
+            Some(bytes) => Ok(Some(rmp_serde::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+}